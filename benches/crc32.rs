@@ -0,0 +1,62 @@
+//! `crc32::calc_crc32` compared to byte at a time table loop and `crc32fast`
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::firmware;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use esp_hal_ota::crc32::calc_crc32;
+
+/// Previous implementation (one table lookup per byte)
+fn bytewise_crc32(buf: &[u8], crc: u32) -> u32 {
+    const fn table() -> [u32; 256] {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = match crc & 1 {
+                    1 => (crc >> 1) ^ 0xEDB88320,
+                    _ => crc >> 1,
+                };
+                bit += 1;
+            }
+
+            table[i] = crc;
+            i += 1;
+        }
+
+        table
+    }
+    static TABLE: [u32; 256] = table();
+
+    let mut crc = !crc;
+    for &byte in buf {
+        crc = TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+
+    !crc
+}
+
+fn crc32(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc32");
+    for len in [256, 4096, 65536] {
+        let data = firmware(len);
+        group.throughput(Throughput::Bytes(len as u64));
+
+        group.bench_with_input(BenchmarkId::new("calc_crc32", len), &data, |b, data| {
+            b.iter(|| calc_crc32(std::hint::black_box(data), 0))
+        });
+        group.bench_with_input(BenchmarkId::new("bytewise", len), &data, |b, data| {
+            b.iter(|| bytewise_crc32(std::hint::black_box(data), 0))
+        });
+        group.bench_with_input(BenchmarkId::new("crc32fast", len), &data, |b, data| {
+            b.iter(|| crc32fast::hash(std::hint::black_box(data)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, crc32);
+criterion_main!(benches);
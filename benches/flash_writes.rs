@@ -0,0 +1,71 @@
+//! Sequential update on mock flash with different transport chunk sizes
+//!
+//! Prints flash operations of each run (erases are counted like esp-storage does them)
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::*;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use esp_hal_ota::Ota;
+
+const CHUNK_SIZES: [usize; 5] = [64, 536, 1460, 4096, 8192];
+
+/// Returns flash operations done by writing the image (without otadata update)
+fn update(flash: &MockFlash, image: &[u8], chunk_size: usize) -> FlashStats {
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.ota_begin(image.len() as u32, crc(image)).unwrap();
+    flash.take_stats();
+    for chunk in image.chunks(chunk_size) {
+        ota.ota_write_chunk(chunk).unwrap();
+    }
+
+    let stats = flash.take_stats();
+    ota.ota_flush(false, true).unwrap();
+    stats
+}
+
+fn flash_writes(c: &mut Criterion) {
+    let image = firmware(OTA_SIZE as usize / 2);
+
+    println!("image: {} bytes", image.len());
+    println!("chunk size | writes | sector erases");
+    for chunk_size in CHUNK_SIZES {
+        let stats = update(&MockFlash::new(), &image, chunk_size);
+        let erases = stats.erases_in(ota_offset(0), image.len() as u32);
+        println!("{chunk_size:>10} | {:>6} | {erases:>13}", stats.writes);
+    }
+
+    // same image flashed again
+    let flash = MockFlash::new();
+    update(&flash, &image, 4096);
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.set_compare_before_write(true);
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    flash.take_stats();
+    ota.ota_write_chunk(&image).unwrap();
+    let stats = flash.take_stats();
+    println!(
+        "re-flash with compare: {} writes, {} sector erases, {:?}",
+        stats.writes,
+        stats.erases_in(ota_offset(0), image.len() as u32),
+        ota.get_write_stats().unwrap()
+    );
+
+    let mut group = c.benchmark_group("ota_write_chunk");
+    group.throughput(Throughput::Bytes(image.len() as u64));
+    for chunk_size in CHUNK_SIZES {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(chunk_size),
+            &chunk_size,
+            |b, &chunk_size| {
+                let flash = MockFlash::new();
+                b.iter(|| update(&flash, &image, chunk_size));
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, flash_writes);
+criterion_main!(benches);
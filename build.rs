@@ -0,0 +1,23 @@
+const CHIPS: [&str; 10] = [
+    "esp32", "esp32s2", "esp32s3", "esp32c2", "esp32c3", "esp32c6", "esp32h2", "esp32c5",
+    "esp32c61", "esp32p4",
+];
+
+fn main() {
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rustc-check-cfg=cfg(has_mmu)");
+    println!("cargo::rustc-check-cfg=cfg(rom_crc32)");
+
+    // MMU (and running partition lookup) is only available when building for a chip
+    let chip_selected = CHIPS
+        .iter()
+        .any(|chip| std::env::var_os(format!("CARGO_FEATURE_{}", chip.to_uppercase())).is_some());
+    if chip_selected {
+        println!("cargo::rustc-cfg=has_mmu");
+    }
+
+    // ROM functions can only be linked on chip, host builds keep using lookup tables
+    if chip_selected && std::env::var_os("CARGO_FEATURE_ROM_CRC32").is_some() {
+        println!("cargo::rustc-cfg=rom_crc32");
+    }
+}
@@ -1,3 +1,10 @@
+//! CRC-32 (ISO-HDLC, same as zlib or `crc32fast`) of image data.
+//!
+//! Calculated using slice-by-8 tables (8 bytes per step), or with `rom-crc32` feature
+//! by `esp_rom_crc32_le` from chip ROM (needs ROM linker scripts of esp-hal). Without
+//! chip feature (e.g. on host) `rom-crc32` is ignored.
+
+#[cfg(not(rom_crc32))]
 const CRC_32_TAB: [u32; 256] = [
     0x00000000, 0x77073096, 0xee0e612c, 0x990951ba, 0x076dc419, 0x706af48f, 0xe963a535, 0x9e6495a3,
     0x0edb8832, 0x79dcb8a4, 0xe0d5e91e, 0x97d2d988, 0x09b64c2b, 0x7eb17cbd, 0xe7b82d07, 0x90bf1d91,
@@ -33,12 +40,64 @@ const CRC_32_TAB: [u32; 256] = [
     0xb3667a2e, 0xc4614ab8, 0x5d681b02, 0x2a6f2b94, 0xb40bbe37, 0xc30c8ea1, 0x5a05df1b, 0x2d02ef8d,
 ];
 
+/// Table `n` gives crc of byte followed by `n` zero bytes (table 0 is [`CRC_32_TAB`])
+#[cfg(not(rom_crc32))]
+static CRC_32_TABLES: [[u32; 256]; 8] = slice_tables();
+
+#[cfg(not(rom_crc32))]
+const fn slice_tables() -> [[u32; 256]; 8] {
+    let mut tables = [CRC_32_TAB; 8];
+    let mut t = 1;
+    while t < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let prev = tables[t - 1][i];
+            tables[t][i] = (prev >> 8) ^ CRC_32_TAB[(prev & 0xFF) as usize];
+            i += 1;
+        }
+
+        t += 1;
+    }
+
+    tables
+}
+
+/// Continues crc of previous data (`crc` is 0 for first chunk)
+#[cfg(not(rom_crc32))]
 pub fn calc_crc32(buf: &[u8], mut crc: u32) -> u32 {
+    let t = &CRC_32_TABLES;
+
     crc = !crc;
-    for &elem in buf {
+    let mut chunks = buf.chunks_exact(8);
+    for chunk in &mut chunks {
+        let lo = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) ^ crc;
+        let hi = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+
+        crc = t[7][(lo & 0xFF) as usize]
+            ^ t[6][((lo >> 8) & 0xFF) as usize]
+            ^ t[5][((lo >> 16) & 0xFF) as usize]
+            ^ t[4][(lo >> 24) as usize]
+            ^ t[3][(hi & 0xFF) as usize]
+            ^ t[2][((hi >> 8) & 0xFF) as usize]
+            ^ t[1][((hi >> 16) & 0xFF) as usize]
+            ^ t[0][(hi >> 24) as usize];
+    }
+
+    for &elem in chunks.remainder() {
         let crc_idx = (crc ^ elem as u32) & 0xFF;
         crc = CRC_32_TAB[crc_idx as usize] ^ (crc >> 8);
     }
 
     crc ^ 0xFFFFFFFF
 }
+
+/// Continues crc of previous data (`crc` is 0 for first chunk)
+#[cfg(rom_crc32)]
+pub fn calc_crc32(buf: &[u8], crc: u32) -> u32 {
+    unsafe extern "C" {
+        fn esp_rom_crc32_le(crc: u32, buf: *const u8, len: u32) -> u32;
+    }
+
+    // ROM function inverts crc at start and end too
+    unsafe { esp_rom_crc32_le(crc, buf.as_ptr(), buf.len() as u32) }
+}
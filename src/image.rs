@@ -0,0 +1,189 @@
+//! App image parsing and validation (like `esp_image_verify` in ESP-IDF).
+
+use crate::{
+    EspImageHeader, EspImageSegmentHeader, ImageInfo, OTA_VERIFY_READ_SIZE, Ota, OtaError, Result,
+};
+use embedded_storage::{ReadStorage, Storage};
+use sha2::{Digest, Sha256};
+
+/// Initial value of image checksum (`ESP_ROM_CHECKSUM_INITIAL`)
+const CHECKSUM_INITIAL: u8 = 0xEF;
+
+impl<S> Ota<S>
+where
+    S: ReadStorage + Storage,
+{
+    /// Reads app image header and segment headers of OTA partition
+    ///
+    /// Image size is calculated from segments (checksum and digest aren't checked,
+    /// use [`Ota::verify_image`] for that)
+    pub fn read_image_info(&mut self, partition: usize) -> Result<ImageInfo> {
+        self.walk_image(partition, None)
+    }
+
+    /// Reads and validates app image in OTA partition: header, segments, checksum
+    /// and appended SHA-256 (if present)
+    pub fn verify_image(&mut self, partition: usize) -> Result<ImageInfo> {
+        self.verify_image_digest(partition).map(|(info, _)| info)
+    }
+
+    /// Calculates SHA-256 of app image in OTA partition (like `esp_partition_get_sha256`)
+    ///
+    /// Digest covers exact image length up to its checksum (appended SHA-256 isn't
+    /// included, so for images with one it's the same value). Image is validated first.
+    pub fn slot_sha256(&mut self, partition: usize) -> Result<[u8; 32]> {
+        self.verify_image_digest(partition)
+            .map(|(_, digest)| digest)
+    }
+
+    /// Calculates SHA-256 of currently running app image, see [`Ota::slot_sha256`]
+    pub fn running_image_sha256(&mut self) -> Result<[u8; 32]> {
+        let partition = self
+            .get_currently_booted_partition()
+            .ok_or(OtaError::CannotFindCurrentBootPartition)?;
+
+        self.slot_sha256(partition)
+    }
+
+    /// Sets OTA partition with already present image as boot partition (without
+    /// downloading anything, like `otatool.py switch_ota_partition`)
+    ///
+    /// Image is validated using [`Ota::verify_image`] first. Image staged in `partition`
+    /// (see [`Ota::ota_stage`]) or journaled update of it is discarded.
+    pub fn switch_to(&mut self, partition: usize, rollback: bool) -> Result<()> {
+        self.verify_image(partition)?;
+        self.set_target_ota_boot_partition(partition, crate::img_state(rollback))?;
+
+        if self
+            .staged
+            .is_some_and(|staged| staged.partition == partition)
+        {
+            self.staged = None;
+        }
+
+        if self
+            .journal_last()?
+            .is_some_and(|(_, record)| record.target_partition as usize == partition)
+        {
+            self.journal_clear()?;
+        }
+
+        info!("[OTA] Switched boot partition to {}", partition);
+        Ok(())
+    }
+
+    /// Validates image and returns its info with calculated SHA-256 of image without
+    /// appended digest
+    fn verify_image_digest(&mut self, partition: usize) -> Result<(ImageInfo, [u8; 32])> {
+        let mut sha = Sha256::new();
+        let info = self.walk_image(partition, Some(&mut sha))?;
+
+        let calculated = <[u8; 32]>::from(sha.finalize());
+        if info.sha256.is_some_and(|hash| hash != calculated) {
+            error!("[OTA] Image SHA-256 mismatch in partition {}!", partition);
+
+            return Err(OtaError::ImageDigestMismatch { partition });
+        }
+
+        Ok((info, calculated))
+    }
+
+    /// Returns image info, if `sha` is given, image data (without appended digest) is fed
+    /// into it and checksum is validated
+    fn walk_image(&mut self, partition: usize, mut sha: Option<&mut Sha256>) -> Result<ImageInfo> {
+        if partition >= self.pinfo.ota_partitions_count {
+            return Err(OtaError::InvalidPartition { partition });
+        }
+
+        let (part_offset, part_size) = self.pinfo.ota_partitions[partition];
+        let invalid = OtaError::InvalidImage { partition };
+
+        let mut bytes = [0; EspImageHeader::SIZE];
+        self.read_flash(part_offset, &mut bytes)?;
+        let header = EspImageHeader::from_bytes(&bytes);
+        if header.magic != EspImageHeader::MAGIC
+            || header.segment_count == 0
+            || header.segment_count > EspImageHeader::MAX_SEGMENTS
+        {
+            return Err(invalid);
+        }
+
+        let mut checksum = CHECKSUM_INITIAL;
+        if let Some(sha) = sha.as_deref_mut() {
+            sha.update(bytes);
+        }
+
+        let mut len = EspImageHeader::SIZE as u32;
+        for _ in 0..header.segment_count {
+            let mut bytes = [0; EspImageSegmentHeader::SIZE];
+            self.read_flash(part_offset + len, &mut bytes)?;
+            let segment = EspImageSegmentHeader::from_bytes(&bytes);
+
+            len += EspImageSegmentHeader::SIZE as u32;
+            if segment.data_len > part_size.saturating_sub(len) {
+                return Err(invalid);
+            }
+
+            if let Some(sha) = sha.as_deref_mut() {
+                sha.update(bytes);
+                checksum = self.hash_range(part_offset + len, segment.data_len, sha, checksum)?;
+            }
+
+            len += segment.data_len;
+        }
+
+        // checksum byte is placed at the end of 16 byte aligned block
+        let unpadded_len = len;
+        len = (len + 1).next_multiple_of(16);
+
+        let mut sha256 = None;
+        let size = len + if header.hash_appended == 1 { 32 } else { 0 };
+        if size > part_size {
+            return Err(invalid);
+        }
+
+        if header.hash_appended == 1 {
+            let mut hash = [0; 32];
+            self.read_flash(part_offset + len, &mut hash)?;
+            sha256 = Some(hash);
+        }
+
+        if let Some(sha) = sha {
+            let padding = len - unpadded_len - 1;
+            self.hash_range(part_offset + unpadded_len, padding, sha, 0)?;
+
+            let mut stored_checksum = [0; 1];
+            self.read_flash(part_offset + len - 1, &mut stored_checksum)?;
+            sha.update(stored_checksum);
+            if stored_checksum[0] != checksum {
+                error!("[OTA] Image checksum mismatch in partition {}!", partition);
+
+                return Err(OtaError::ImageDigestMismatch { partition });
+            }
+        }
+
+        Ok(ImageInfo {
+            header,
+            size,
+            sha256,
+        })
+    }
+
+    /// Feeds flash range into sha, returns xor checksum of its bytes
+    fn hash_range(&mut self, offset: u32, len: u32, sha: &mut Sha256, checksum: u8) -> Result<u8> {
+        let mut checksum = checksum;
+        let mut bytes = [0; OTA_VERIFY_READ_SIZE];
+
+        let mut pos = 0;
+        while pos < len {
+            let n = (len - pos).min(OTA_VERIFY_READ_SIZE as u32) as usize;
+            self.read_flash(offset + pos, &mut bytes[..n])?;
+
+            sha.update(&bytes[..n]);
+            checksum = bytes[..n].iter().fold(checksum, |acc, b| acc ^ b);
+            pos += n as u32;
+        }
+
+        Ok(checksum)
+    }
+}
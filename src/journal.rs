@@ -0,0 +1,254 @@
+//! Optional resume journal for interrupted downloads.
+//!
+//! Progress of running update is checkpointed into reserved flash sector as a list of
+//! [`OtaJournalRecord`]s (newest last), so it survives reset and can be resumed using
+//! [`Ota::ota_resume_from_journal`].
+
+use crate::{
+    BlockBitmap, OTA_SIZE_UNKNOWN, Ota, OtaError, OtaJournalRecord, OtaResumeInfo, PART_OFFSET,
+    PART_SIZE, PartitionEntry, Result, StagedImage,
+};
+use embedded_storage::{ReadStorage, Storage};
+
+/// Size of flash region reserved for journal (one sector)
+pub const JOURNAL_SIZE: u32 = 0x1000;
+
+/// Progress is checkpointed every time this many bytes are written
+pub const JOURNAL_INTERVAL: u32 = 0x10000;
+
+const JOURNAL_RECORDS: u32 = JOURNAL_SIZE / OtaJournalRecord::SIZE as u32;
+
+static EMPTY_JOURNAL: [u8; JOURNAL_SIZE as usize] = [0xFF; JOURNAL_SIZE as usize];
+
+const DATA_PARTITION_TYPE: u8 = 1;
+/// `undefined` data subtype, other ones are used by ESP-IDF (otadata, nvs, fat, ...)
+const UNDEFINED_DATA_SUBTYPE: u8 = 0x06;
+
+impl<S> Ota<S>
+where
+    S: ReadStorage + Storage,
+{
+    /// Same as [`Ota::new`], but update progress is also journaled into
+    /// [`JOURNAL_SIZE`] bytes of flash at `journal_offset`
+    ///
+    /// Journal region must be sector aligned and lie inside `undefined` data partition from
+    /// partition table, for example:
+    /// `ota_journal, data, undefined, 0xf000, 0x1000`
+    pub fn new_with_journal(flash: S, journal_offset: u32) -> Result<Self> {
+        let mut ota = Self::new(flash)?;
+
+        let valid = journal_offset.is_multiple_of(JOURNAL_SIZE)
+            && journal_offset >= PART_OFFSET + PART_SIZE
+            && ota
+                .find_journal_partition(journal_offset)?
+                .is_some_and(|entry| {
+                    entry.p_type == DATA_PARTITION_TYPE && entry.p_subtype == UNDEFINED_DATA_SUBTYPE
+                });
+        if !valid {
+            error!("[OTA] Invalid journal region at 0x{:x}!", journal_offset);
+
+            return Err(OtaError::InvalidJournalRegion {
+                offset: journal_offset,
+            });
+        }
+
+        ota.journal_offset = Some(journal_offset);
+        Ok(ota)
+    }
+
+    /// Checks journal for interrupted update
+    ///
+    /// Already written part of image is read back and checked against journaled crc.
+    /// If it's intact update continues where it was interrupted, otherwise it starts
+    /// again from offset 0. In both cases [`OtaResumeInfo::next_offset`] tells which
+    /// offset of image should be sent next (compare `image_size` and `target_crc`
+    /// with server to make sure it's still the same image).
+    ///
+    /// Returns `None` if journal isn't enabled or there is no interrupted update.
+    pub fn ota_resume_from_journal(&mut self) -> Result<Option<OtaResumeInfo>> {
+        let Some((_, record)) = self.journal_last()? else {
+            return Ok(None);
+        };
+
+        let target_partition = record.target_partition as usize;
+        if target_partition >= self.pinfo.ota_partitions_count {
+            warn!("[OTA] Journaled partition doesn't exist! Discarding journal...");
+            self.journal_clear()?;
+
+            return Ok(None);
+        }
+
+        // update was already activated (e.g. reset before journal was cleared)
+        if let Err(OtaError::PartitionRunning { .. }) = self.check_target_slot(target_partition) {
+            warn!("[OTA] Journaled partition is running! Discarding journal...");
+            self.journal_clear()?;
+
+            return Ok(None);
+        }
+
+        let (ota_offset, ota_size) = self.pinfo.ota_partitions[target_partition];
+        let size_unknown = record.image_size == OTA_SIZE_UNKNOWN;
+        let flash_size = match size_unknown {
+            true => ota_size,
+            false => record.image_size,
+        };
+
+        let mut written = record.written.min(flash_size);
+        let mut last_crc = record.last_crc;
+        if self.calc_partition_crc(target_partition, written)? != last_crc {
+            warn!("[OTA] Written data doesn't match journal! Restarting update...");
+
+            written = 0;
+            last_crc = 0;
+        }
+
+        let mut blocks = BlockBitmap::new();
+        blocks.mark_prefix(written, record.image_size);
+        self.progress = Some(crate::FlashProgress {
+            last_crc,
+            flash_offset: ota_offset + written,
+            flash_size,
+            remaining: flash_size - written,
+            target_partition,
+            target_crc: record.target_crc,
+            size_unknown,
+            blocks,
+            random_access: false,
+            stats: crate::WriteStats::default(),
+        });
+
+        info!("[OTA] Resuming update at offset {}", written);
+        Ok(Some(OtaResumeInfo {
+            target_partition,
+            image_size: record.image_size,
+            target_crc: record.target_crc,
+            next_offset: written,
+        }))
+    }
+
+    /// Returns image staged with `ota_stage` (complete update recorded in journal)
+    pub(crate) fn journal_staged_image(&mut self) -> Result<Option<StagedImage>> {
+        let Some((_, record)) = self.journal_last()? else {
+            return Ok(None);
+        };
+
+        if record.image_size == OTA_SIZE_UNKNOWN
+            || record.written != record.image_size
+            || record.target_partition as usize >= self.pinfo.ota_partitions_count
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(StagedImage {
+            partition: record.target_partition as usize,
+            size: record.image_size,
+            crc: record.target_crc,
+        }))
+    }
+
+    /// Appends current progress to journal (no-op if journal isn't enabled)
+    ///
+    /// NOTE: out of order updates (`ota_write_at`) aren't journaled
+    pub(crate) fn journal_checkpoint(&mut self) -> Result<()> {
+        let (Some(journal_offset), Some(progress)) = (self.journal_offset, self.progress.as_ref())
+        else {
+            return Ok(());
+        };
+
+        let record = OtaJournalRecord {
+            target_partition: progress.target_partition as u32,
+            image_size: match progress.size_unknown {
+                true => OTA_SIZE_UNKNOWN,
+                false => progress.flash_size,
+            },
+            target_crc: progress.target_crc,
+            written: progress.flash_size - progress.remaining,
+            last_crc: progress.last_crc,
+        };
+
+        let idx = match self.journal_last()? {
+            Some((idx, _)) if idx + 1 < JOURNAL_RECORDS => idx + 1,
+            Some(_) => {
+                self.journal_clear()?;
+                0
+            }
+            None => 0,
+        };
+
+        debug!("[OTA] Journal checkpoint at {}", record.written);
+        self.write_flash(
+            journal_offset + idx * OtaJournalRecord::SIZE as u32,
+            &record.to_bytes(),
+        )
+    }
+
+    /// Clears journal (no-op if journal isn't enabled)
+    pub(crate) fn journal_clear(&mut self) -> Result<()> {
+        let Some(journal_offset) = self.journal_offset else {
+            return Ok(());
+        };
+
+        let mut bytes = [0; OtaJournalRecord::SIZE];
+        self.read_flash(journal_offset, &mut bytes)?;
+        if bytes == [0xFF; OtaJournalRecord::SIZE] {
+            return Ok(());
+        }
+
+        // whole sector at once, so it's erased only once
+        self.write_flash(journal_offset, &EMPTY_JOURNAL)
+    }
+
+    /// Returns partition table entry that contains whole journal region, `None` if there
+    /// is no such entry or region overlaps any other partition
+    fn find_journal_partition(&mut self, journal_offset: u32) -> Result<Option<PartitionEntry>> {
+        let journal = journal_offset..journal_offset + JOURNAL_SIZE;
+
+        let mut found = None;
+        let mut bytes = [0; PartitionEntry::SIZE];
+        for read_offset in (0..PART_SIZE).step_by(PartitionEntry::SIZE) {
+            self.read_flash(PART_OFFSET + read_offset, &mut bytes)?;
+            if bytes == [0xFF; PartitionEntry::SIZE] {
+                break;
+            }
+
+            let Some(entry) = PartitionEntry::from_bytes(&bytes) else {
+                continue;
+            };
+
+            let partition = entry.offset..entry.offset + entry.size;
+            if partition.start <= journal.start && journal.end <= partition.end {
+                found = Some(entry);
+            } else if journal.start < partition.end && partition.start < journal.end {
+                return Ok(None);
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Returns index and content of newest valid journal record
+    pub(crate) fn journal_last(&mut self) -> Result<Option<(u32, OtaJournalRecord)>> {
+        let Some(journal_offset) = self.journal_offset else {
+            return Ok(None);
+        };
+
+        let mut last = None;
+        let mut bytes = [0; OtaJournalRecord::SIZE];
+        for idx in 0..JOURNAL_RECORDS {
+            self.read_flash(
+                journal_offset + idx * OtaJournalRecord::SIZE as u32,
+                &mut bytes,
+            )?;
+
+            if bytes == [0xFF; OtaJournalRecord::SIZE] {
+                break;
+            }
+
+            if let Some(record) = OtaJournalRecord::from_bytes(&bytes) {
+                last = Some((idx, record));
+            }
+        }
+
+        Ok(last)
+    }
+}
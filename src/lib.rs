@@ -1,5 +1,4 @@
 #![no_std]
-#![cfg_attr(any(feature = "esp32", feature = "esp32s2"), feature(concat_idents))]
 #![cfg_attr(feature = "esp32", feature(asm_experimental_arch))]
 #![doc = include_str!("../README.md")]
 
@@ -11,15 +10,27 @@ pub use structs::*;
 
 pub mod crc32;
 pub mod helpers;
+pub mod image;
+pub mod journal;
 pub mod mmu_hal;
 pub mod mmu_ll;
+pub mod reader;
+pub mod sector;
+pub mod selector;
+pub mod status;
 pub mod structs;
+pub mod supervisor;
+pub mod writer;
 
 const PART_OFFSET: u32 = 0x8000;
 const PART_SIZE: u32 = 0xc00;
 const FIRST_OTA_PART_SUBTYPE: u8 = 0x10;
 const OTA_VERIFY_READ_SIZE: usize = 256;
 
+/// Pass as `size` to [`Ota::ota_begin`] if image size isn't known up front
+/// (like `OTA_SIZE_UNKNOWN` in ESP-IDF). Update must then be finished with [`Ota::ota_finish`].
+pub const OTA_SIZE_UNKNOWN: u32 = 0xFFFFFFFF;
+
 pub struct Ota<S>
 where
     S: ReadStorage + Storage,
@@ -28,25 +39,47 @@ where
 
     progress: Option<FlashProgress>,
     pinfo: PartitionInfo,
+    journal_offset: Option<u32>,
+    staged: Option<StagedImage>,
+    slot_strategy: selector::SlotStrategy,
+    running: Option<(usize, BootPartitionSource)>,
+    sector: sector::SectorBuffer,
+    compare_before_write: bool,
 }
 
 impl<S> Ota<S>
 where
     S: ReadStorage + Storage,
 {
+    /// Reads partition table and detects running partition
+    ///
+    /// NOTE: if running partition can't be found using flash MMU, it's taken from otadata
+    /// (see [`Ota::get_running_partition`]), so `Ota` should be created right after boot,
+    /// before otadata is modified
     pub fn new(mut flash: S) -> Result<Self> {
         let pinfo = Self::read_partitions(&mut flash)?;
         if pinfo.ota_partitions_count < 2 {
             error!("Not enough OTA partitions! (>= 2)");
 
-            return Err(OtaError::NotEnoughPartitions);
+            return Err(OtaError::NotEnoughPartitions {
+                found: pinfo.ota_partitions_count,
+            });
         }
 
-        Ok(Ota {
+        let mut ota = Ota {
             flash,
             progress: None,
             pinfo,
-        })
+            journal_offset: None,
+            staged: None,
+            slot_strategy: selector::SlotStrategy::default(),
+            running: None,
+            sector: sector::SectorBuffer::new(),
+            compare_before_write: false,
+        };
+
+        ota.running = ota.detect_running_partition()?;
+        Ok(ota)
     }
 
     fn get_partitions(&self) -> &[(u32, u32)] {
@@ -54,50 +87,225 @@ where
     }
 
     /// To begin ota update (need to provide flash size)
+    ///
+    /// If size is [`OTA_SIZE_UNKNOWN`], `target_crc` is ignored and both are provided later
+    /// in [`Ota::ota_finish`]. Sectors are erased by the storage driver as data arrives.
+    ///
+    /// Target partition is chosen using strategy set with [`Ota::set_slot_strategy`]
+    /// (next partition after the running one by default).
+    ///
+    /// Fails with [`OtaError::ImageTooLarge`] if image doesn't fit into target partition
     pub fn ota_begin(&mut self, size: u32, target_crc: u32) -> Result<()> {
-        let next_part = self.get_next_ota_partition().unwrap_or(0);
+        let mut strategy = self.slot_strategy;
+        self.ota_begin_with(&mut strategy, size, target_crc)
+    }
+
+    /// Like [`Ota::ota_begin`], but target partition is chosen by given `selector`
+    /// (see [`Ota::select_slot`])
+    pub fn ota_begin_with(
+        &mut self,
+        selector: &mut impl selector::SlotSelector,
+        size: u32,
+        target_crc: u32,
+    ) -> Result<()> {
+        let next_part = self.select_slot(selector)?;
+        self.ota_begin_in(next_part, size, target_crc)
+    }
 
-        let ota_offset = self.get_partitions()[next_part].0;
+    /// Like [`Ota::ota_begin`], but update is written into given OTA partition
+    ///
+    /// Fails with [`OtaError::PartitionRunning`] if `partition` is currently running
+    pub fn ota_begin_in(&mut self, partition: usize, size: u32, target_crc: u32) -> Result<()> {
+        self.check_target_slot(partition)?;
+        self.sector.clear();
+
+        let (ota_offset, ota_size) = self.get_partitions()[partition];
+        if size == OTA_SIZE_UNKNOWN {
+            self.progress = Some(FlashProgress {
+                last_crc: 0,
+                flash_size: ota_size,
+                remaining: ota_size,
+                flash_offset: ota_offset,
+                target_partition: partition,
+                target_crc: 0,
+                size_unknown: true,
+                blocks: BlockBitmap::new(),
+                random_access: false,
+                stats: WriteStats::default(),
+            });
+
+            return self.journal_checkpoint();
+        }
+
+        self.check_image_fits(partition, size)?;
         self.progress = Some(FlashProgress {
             last_crc: 0,
             flash_size: size,
             remaining: size,
             flash_offset: ota_offset,
-            target_partition: next_part,
+            target_partition: partition,
             target_crc,
+            size_unknown: false,
+            blocks: BlockBitmap::new(),
+            random_access: false,
+            stats: WriteStats::default(),
         });
 
+        self.journal_checkpoint()
+    }
+
+    /// Checks that image of `size` bytes fits into OTA `partition` (and into
+    /// [`BlockBitmap::MAX_IMAGE_SIZE`], bitmap can't track blocks past it)
+    fn check_image_fits(&self, partition: usize, size: u32) -> Result<()> {
+        let max_size = self.pinfo.ota_partitions[partition]
+            .1
+            .min(BlockBitmap::MAX_IMAGE_SIZE);
+        if size > max_size {
+            error!("[OTA] Image too large! ({} > {} bytes)", size, max_size);
+
+            return Err(OtaError::ImageTooLarge {
+                image_size: size,
+                slot_size: max_size,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Sets final image size and crc of update started with [`OTA_SIZE_UNKNOWN`]
+    /// (can also be used to double check known size updates)
+    ///
+    /// After this call update can be flushed using [`Ota::ota_flush`]
+    pub fn ota_finish(&mut self, len: u32, target_crc: u32) -> Result<()> {
+        self.flush_sector()?;
+        let progress = self.progress.as_mut().ok_or(OtaError::OtaNotStarted)?;
+
+        let written = progress.flash_size - progress.remaining;
+        if written != len {
+            error!("[OTA] Image size mismatch! ({} != {} bytes)", len, written);
+
+            return Err(OtaError::ImageSizeMismatch {
+                expected: len,
+                written,
+            });
+        }
+
+        if !progress.size_unknown && progress.target_crc != target_crc {
+            return Err(OtaError::WrongCRC {
+                expected: progress.target_crc,
+                calculated: target_crc,
+            });
+        }
+
+        progress.flash_size = len;
+        progress.remaining = 0;
+        progress.target_crc = target_crc;
+        progress.size_unknown = false;
+        progress.blocks.mark_prefix(len, len);
+
         Ok(())
     }
 
     /// Resumes an OTA update after progress has been lost
-    pub fn ota_resume(&mut self, flash_size: u32, remaining: u32, target_crc: u32, last_crc: u32) {
-        let next_part = self.get_next_ota_partition().unwrap_or(0);
-        let ota_offset = self.get_partitions()[next_part].0;
+    ///
+    /// Target partition is chosen the same way as in [`Ota::ota_begin`] (and the same
+    /// selection errors are returned). Updates started with [`Ota::ota_begin_in`] or
+    /// [`Ota::ota_begin_with`] should be resumed with [`Ota::ota_resume_in`] instead.
+    ///
+    /// Fails with [`OtaError::ImageTooLarge`] if `flash_size` doesn't fit into target
+    /// partition and with [`OtaError::InvalidResumeProgress`] if `remaining > flash_size`
+    pub fn ota_resume(
+        &mut self,
+        flash_size: u32,
+        remaining: u32,
+        target_crc: u32,
+        last_crc: u32,
+    ) -> Result<()> {
+        let mut strategy = self.slot_strategy;
+        let next_part = self.select_slot(&mut strategy)?;
+        self.ota_resume_in(next_part, flash_size, remaining, target_crc, last_crc)
+    }
+
+    /// Like [`Ota::ota_resume`], but update is resumed in given OTA partition
+    /// (saved with [`Ota::get_target_partition`])
+    ///
+    /// Fails with [`OtaError::PartitionRunning`] if `partition` is currently running
+    pub fn ota_resume_in(
+        &mut self,
+        partition: usize,
+        flash_size: u32,
+        remaining: u32,
+        target_crc: u32,
+        last_crc: u32,
+    ) -> Result<()> {
+        self.check_target_slot(partition)?;
+        let ota_offset = self.get_partitions()[partition].0;
+
+        self.check_image_fits(partition, flash_size)?;
+        if remaining > flash_size {
+            error!(
+                "[OTA] Invalid resume progress! ({} of {} bytes remaining)",
+                remaining, flash_size
+            );
+
+            return Err(OtaError::InvalidResumeProgress {
+                flash_size,
+                remaining,
+            });
+        }
+
+        let written = flash_size - remaining;
+
+        let mut blocks = BlockBitmap::new();
+        blocks.mark_prefix(written, flash_size);
+        self.sector.clear();
         self.progress = Some(FlashProgress {
             last_crc,
             flash_size,
             remaining,
-            flash_offset: ota_offset,
-            target_partition: next_part,
+            flash_offset: ota_offset + written,
+            target_partition: partition,
             target_crc,
+            size_unknown: false,
+            blocks,
+            random_access: false,
+            stats: WriteStats::default(),
         });
+
+        Ok(())
     }
 
     /// Returns progress details to save for resumption later
+    ///
+    /// Only data already written to flash is included (not the buffered part of sector).
+    /// Target partition can be saved too, see [`Ota::get_target_partition`]
     pub fn get_progress_details(&self) -> Option<(u32, u32)> {
         if self.progress.is_none() {
-            #[cfg(feature = "log")]
-            log::warn!("[OTA] Cannot get progress details!");
-
-            return None;
+            warn!("[OTA] Cannot get progress details!");
         }
 
-        let progress = self.progress.as_ref().unwrap();
-        Some((progress.remaining, progress.last_crc))
+        self.progress
+            .as_ref()
+            .map(|progress| (progress.remaining, progress.last_crc))
+    }
+
+    /// Returns OTA partition that running update is written into
+    /// (to be passed to [`Ota::ota_resume_in`])
+    pub fn get_target_partition(&self) -> Option<usize> {
+        self.progress
+            .as_ref()
+            .map(|progress| progress.target_partition)
+    }
+
+    /// Returns number of flash sectors written and skipped by running update
+    /// (see [`Ota::set_compare_before_write`])
+    pub fn get_write_stats(&self) -> Option<WriteStats> {
+        self.progress.as_ref().map(|progress| progress.stats)
     }
 
     /// Returns ota progress in f32 (0..1)
+    ///
+    /// NOTE: always 0 for updates started with [`OTA_SIZE_UNKNOWN`]
     pub fn get_ota_progress(&self) -> f32 {
         if self.progress.is_none() {
             warn!("[OTA] Cannot get ota progress! Seems like update wasn't started yet.");
@@ -106,73 +314,392 @@ where
         }
 
         let progress = self.progress.as_ref().unwrap();
-        (progress.flash_size - progress.remaining) as f32 / progress.flash_size as f32
+        if progress.size_unknown {
+            return 0.0;
+        }
+
+        let written = progress.flash_size - progress.remaining + self.sector.len() as u32;
+        written as f32 / progress.flash_size as f32
     }
 
     /// Writes next firmware chunk
+    ///
+    /// Data is collected into whole flash sectors (see [`sector::SECTOR_SIZE`]) before it's
+    /// written, so storage driver erases every sector once no matter the chunk size (unless
+    /// partial sector is flushed early, see [`sector`]). Last (partial) sector is written
+    /// when image is complete, or in [`Ota::ota_finish`] for updates with unknown size.
     pub fn ota_write_chunk(&mut self, chunk: &[u8]) -> Result<bool> {
-        let progress = self.progress.as_mut().ok_or(OtaError::OtaNotStarted)?;
+        self.write_chunk(chunk)?;
 
-        if progress.remaining == 0 {
-            return Ok(true);
+        let progress = self.progress.as_ref().ok_or(OtaError::OtaNotStarted)?;
+        Ok(progress.remaining == 0 && !progress.size_unknown)
+    }
+
+    /// Same as [`Ota::ota_write_chunk`], but returns number of bytes taken from `chunk`
+    pub(crate) fn write_chunk(&mut self, chunk: &[u8]) -> Result<usize> {
+        let progress = self.progress.as_ref().ok_or(OtaError::OtaNotStarted)?;
+        if progress.random_access {
+            return Err(OtaError::RandomAccessInProgress);
         }
 
-        let write_size = chunk.len() as u32;
-        let write_size = write_size.min(progress.remaining) as usize;
+        // buffered bytes are already accepted, but not counted in progress yet
+        let buffered = self.sector.len() as u32;
+        let remaining = progress.remaining - buffered;
+        let size_unknown = progress.size_unknown;
+        if remaining == 0 && !size_unknown {
+            return Ok(0);
+        }
 
-        self.flash
-            .write(progress.flash_offset, &chunk[..write_size])
-            .map_err(|_| OtaError::FlashRWError)?;
+        // with unknown size whole chunk must fit, the bounds check below rejects it otherwise
+        let write_size = match size_unknown {
+            true => chunk.len(),
+            false => (chunk.len() as u32).min(remaining) as usize,
+        };
 
-        debug!(
-            "[OTA] Wrote {} bytes to ota partition at 0x{:x}",
-            write_size, progress.flash_offset
-        );
+        let (ota_offset, ota_size) = self.pinfo.ota_partitions[progress.target_partition];
+        let write_offset = progress.flash_offset + buffered;
+        let write_end = write_offset as u64 + write_size as u64;
+        if write_end > (ota_offset + ota_size) as u64 {
+            error!(
+                "[OTA] Write at 0x{:x} is outside of target partition!",
+                write_offset
+            );
 
-        progress.last_crc = crc32::calc_crc32(&chunk[..write_size], progress.last_crc);
+            return Err(OtaError::ImageTooLarge {
+                image_size: (write_end - ota_offset as u64) as u32,
+                slot_size: ota_size,
+            });
+        }
+
+        self.buffer_chunk(&chunk[..write_size])?;
+        if write_size as u32 == remaining && !size_unknown {
+            self.flush_sector()?;
+        }
+
+        Ok(write_size)
+    }
+
+    /// Writes firmware chunk at given offset of image (chunks can arrive in any order)
+    ///
+    /// Offset has to be aligned to [`OTA_BLOCK_SIZE`] and chunk has to contain whole blocks
+    /// (except last block of image), so every flash sector is erased and written at once.
+    /// Sequentially written data that is still buffered is written first (as partial sector).
+    /// Whole image crc is checked in [`Ota::ota_flush`] after all blocks arrived.
+    ///
+    /// NOTE: progress isn't journaled and [`Ota::ota_write_chunk`] can't be used anymore
+    ///
+    /// Returns true if all blocks were written
+    pub fn ota_write_at(&mut self, offset: u32, chunk: &[u8]) -> Result<bool> {
+        // sequentially written data must be in flash before blocks are tracked by bitmap
+        self.flush_sector()?;
+        let progress = self.progress.as_ref().ok_or(OtaError::OtaNotStarted)?;
+        if progress.size_unknown {
+            return Err(OtaError::OtaSizeUnknown);
+        }
+
+        let end = offset as u64 + chunk.len() as u64;
+        if end > progress.flash_size as u64 {
+            return Err(OtaError::ImageTooLarge {
+                image_size: end as u32,
+                slot_size: progress.flash_size,
+            });
+        }
+
+        if !offset.is_multiple_of(OTA_BLOCK_SIZE)
+            || (!chunk.len().is_multiple_of(OTA_BLOCK_SIZE as usize)
+                && end != progress.flash_size as u64)
+        {
+            error!("[OTA] Unaligned write at 0x{:x}!", offset);
+
+            return Err(OtaError::UnalignedWrite { offset });
+        }
+
+        // `end` fits into u32, it's not past `flash_size`
+        self.check_image_fits(progress.target_partition, end as u32)?;
+
+        let progress = self.progress.as_mut().ok_or(OtaError::OtaNotStarted)?;
+        let flash_offset = self.pinfo.ota_partitions[progress.target_partition].0 + offset;
+        Self::program(
+            &mut self.flash,
+            self.compare_before_write,
+            flash_offset,
+            chunk,
+            &mut progress.stats,
+        )?;
+
+        progress.random_access = true;
+        for block in 0..BlockBitmap::blocks_for(chunk.len() as u32) {
+            progress.blocks.set(offset / OTA_BLOCK_SIZE + block);
+        }
+
+        progress.remaining =
+            progress.flash_size - progress.blocks.covered_bytes(progress.flash_size);
 
-        progress.flash_offset += write_size as u32;
-        progress.remaining -= write_size as u32;
         Ok(progress.remaining == 0)
     }
 
+    /// Returns byte ranges of image that weren't written yet
+    pub fn missing_ranges(&self) -> Result<MissingRanges<'_>> {
+        let progress = self.progress.as_ref().ok_or(OtaError::OtaNotStarted)?;
+        if progress.size_unknown {
+            return Err(OtaError::OtaSizeUnknown);
+        }
+
+        Ok(progress.blocks.missing_ranges(progress.flash_size))
+    }
+
     /// verify - should it read flash and check crc
     /// rollback - if rollbacks enable (will set ota_state to ESP_OTA_IMG_NEW)
     pub fn ota_flush(&mut self, verify: bool, rollback: bool) -> Result<()> {
-        if verify && !self.ota_verify()? {
-            error!("[OTA] Verify failed! Not flushing...");
+        let progress = self.check_written_image(verify)?;
 
-            return Err(OtaError::OtaVerifyError);
+        self.set_target_ota_boot_partition(progress.target_partition, img_state(rollback))?;
+        self.staged = None;
+        self.journal_clear()
+    }
+
+    /// Same as [`Ota::ota_flush`], but otadata isn't changed. Image is only staged
+    /// and can be activated later using [`Ota::ota_activate`].
+    ///
+    /// If journal is enabled, staged image is recorded there, so it can be activated
+    /// even after reset.
+    ///
+    /// Returns index of partition with staged image
+    pub fn ota_stage(&mut self, verify: bool) -> Result<usize> {
+        let progress = self.check_written_image(verify)?;
+
+        self.staged = Some(StagedImage {
+            partition: progress.target_partition,
+            size: progress.flash_size,
+            crc: progress.target_crc,
+        });
+
+        self.journal_checkpoint()?;
+
+        info!(
+            "[OTA] Image staged in partition {}",
+            progress.target_partition
+        );
+        Ok(progress.target_partition)
+    }
+
+    /// Returns image staged using [`Ota::ota_stage`] (also from journal, if enabled)
+    pub fn get_staged_image(&mut self) -> Result<Option<StagedImage>> {
+        if self.staged.is_some() {
+            return Ok(self.staged);
+        }
+
+        self.journal_staged_image()
+    }
+
+    /// Sets staged image in `partition` as boot partition
+    ///
+    /// Crc of staged image is checked again before switching
+    pub fn ota_activate(&mut self, partition: usize, rollback: bool) -> Result<()> {
+        let staged = self
+            .get_staged_image()?
+            .filter(|staged| staged.partition == partition)
+            .ok_or(OtaError::NotStaged { partition })?;
+
+        let calculated = self.calc_partition_crc(partition, staged.size)?;
+        if calculated != staged.crc {
+            error!("[OTA] Staged image is corrupted! Not activating...");
+
+            return Err(OtaError::OtaVerifyError {
+                expected: staged.crc,
+                calculated,
+            });
         }
 
+        self.set_target_ota_boot_partition(partition, img_state(rollback))?;
+        self.staged = None;
+        self.journal_clear()?;
+
+        info!("[OTA] Activated partition {}", partition);
+        Ok(())
+    }
+
+    /// Checks that whole image was written and its crc matches
+    fn check_written_image(&mut self, verify: bool) -> Result<FlashProgress> {
+        self.flush_sector()?;
         let progress = self.progress.clone().ok_or(OtaError::OtaNotStarted)?;
+        if progress.size_unknown {
+            error!("[OTA] Image size unknown! Call ota_finish first...");
+
+            return Err(OtaError::OtaNotFinished);
+        }
+
+        if progress.random_access {
+            if let Some(missing) = self.missing_ranges()?.next() {
+                error!("[OTA] Image is incomplete! Not flushing...");
+
+                return Err(OtaError::MissingBlocks {
+                    offset: missing.start,
+                });
+            }
+
+            // blocks were written out of order, so crc can only be calculated from flash
+            let calculated = self.calc_written_crc()?;
+            if calculated != progress.target_crc {
+                error!("[OTA] Crc check failed! Cant finish ota update...");
+
+                return Err(OtaError::WrongCRC {
+                    expected: progress.target_crc,
+                    calculated,
+                });
+            }
+        } else if verify {
+            let calculated = self.calc_written_crc()?;
+            if calculated != progress.target_crc {
+                error!("[OTA] Verify failed! Not flushing...");
+
+                return Err(OtaError::OtaVerifyError {
+                    expected: progress.target_crc,
+                    calculated,
+                });
+            }
+        }
 
-        if progress.target_crc != progress.last_crc {
+        if !progress.random_access && progress.target_crc != progress.last_crc {
             warn!("[OTA] Calculated crc: {}", progress.last_crc);
             warn!("[OTA] Target crc: {}", progress.target_crc);
             error!("[OTA] Crc check failed! Cant finish ota update...");
 
-            return Err(OtaError::WrongCRC);
+            return Err(OtaError::WrongCRC {
+                expected: progress.target_crc,
+                calculated: progress.last_crc,
+            });
         }
 
-        let img_state = match rollback {
-            true => OtaImgState::EspOtaImgNew,
-            false => OtaImgState::EspOtaImgUndefined,
+        Ok(progress)
+    }
+
+    /// It reads written flash and checks crc
+    pub fn ota_verify(&mut self) -> Result<bool> {
+        self.flush_sector()?;
+        let target_crc = self
+            .progress
+            .as_ref()
+            .ok_or(OtaError::OtaNotStarted)?
+            .target_crc;
+
+        Ok(self.calc_written_crc()? == target_crc)
+    }
+
+    /// Cancels running OTA update
+    ///
+    /// If update was already flushed (target partition set as next boot partition),
+    /// its otadata entry is marked as [`OtaImgState::EspOtaImgAborted`] so bootloader
+    /// won't boot it. If `erase` is true, written part of target partition is erased.
+    /// Resume journal is cleared.
+    ///
+    /// Does nothing (except clearing journal) if no update is running
+    pub fn ota_abort(&mut self, erase: bool) -> Result<()> {
+        self.journal_clear()?;
+        self.staged = None;
+        self.sector.clear();
+
+        let Some(progress) = self.progress.take() else {
+            return Ok(());
         };
 
-        self.set_target_ota_boot_partition(progress.target_partition, img_state);
+        if let Some((slot, entry)) = self.get_latest_slot()?
+            && helpers::seq_to_part(entry.seq, self.pinfo.ota_partitions_count)
+                == progress.target_partition
+            && self.get_currently_booted_partition() != Some(progress.target_partition)
+        {
+            self.set_ota_state(slot, OtaImgState::EspOtaImgAborted)?;
+
+            info!(
+                "[OTA] Marked aborted update as aborted in otadata slot {}",
+                slot
+            );
+        }
+
+        if erase {
+            let ota_offset = self.pinfo.ota_partitions[progress.target_partition].0;
+            let written = match progress.random_access {
+                true => progress.flash_size,
+                false => progress.flash_offset - ota_offset,
+            };
+
+            self.erase_flash(ota_offset, written)?;
+        }
+
+        info!("[OTA] Update aborted!");
         Ok(())
     }
 
-    /// It reads written flash and checks crc
-    pub fn ota_verify(&mut self) -> Result<bool> {
+    /// Erases whole OTA partition (like `otatool.py erase_ota_partition`)
+    ///
+    /// Refuses to erase currently running partition (and fails with
+    /// [`OtaError::CannotFindCurrentBootPartition`] if it isn't known). Running or staged
+    /// update targeting `partition` is aborted first (see [`Ota::ota_abort`]). Otadata isn't
+    /// touched, so if it still points to erased partition, bootloader falls back to other
+    /// valid image.
+    ///
+    /// NOTE: partition is erased by writing 0xFF (see [`Ota::reset_otadata`])
+    pub fn erase_slot(&mut self, partition: usize) -> Result<()> {
+        if partition >= self.pinfo.ota_partitions_count {
+            return Err(OtaError::InvalidPartition { partition });
+        }
+
+        let running = self.get_currently_booted_partition();
+        if running.is_none() {
+            error!("[OTA] Running partition unknown, refusing to erase!");
+        }
+
+        if running.ok_or(OtaError::CannotFindCurrentBootPartition)? == partition {
+            error!("[OTA] Refusing to erase running partition {}!", partition);
+
+            return Err(OtaError::PartitionRunning { partition });
+        }
+
+        let in_progress = self
+            .progress
+            .as_ref()
+            .is_some_and(|progress| progress.target_partition == partition);
+        let in_journal = self
+            .journal_last()?
+            .is_some_and(|(_, record)| record.target_partition as usize == partition);
+        if in_progress || in_journal || self.staged.is_some_and(|s| s.partition == partition) {
+            self.ota_abort(false)?;
+        }
+
+        let (offset, size) = self.pinfo.ota_partitions[partition];
+        self.erase_flash(offset, size)?;
+
+        info!("[OTA] Erased partition {}", partition);
+        Ok(())
+    }
+
+    /// Erases otadata partition (like `otatool.py erase_otadata`)
+    ///
+    /// Bootloader will then boot factory app (or `ota_0` if there is no factory partition)
+    ///
+    /// NOTE: `Storage` has no erase operation, so sectors are filled with 0xFF instead.
+    /// Drivers like esp-storage erase every sector before writing it, so this ends up as
+    /// one erase (plus write of 0xFF) of every sector that isn't erased already.
+    pub fn reset_otadata(&mut self) -> Result<()> {
+        self.erase_flash(self.pinfo.otadata_offset, self.pinfo.otadata_size)?;
+
+        info!("[OTA] Otadata erased");
+        Ok(())
+    }
+
+    /// Reads written flash of target partition and calculates its crc
+    fn calc_written_crc(&mut self) -> Result<u32> {
         let progress = self.progress.clone().ok_or(OtaError::OtaNotStarted)?;
+        self.calc_partition_crc(progress.target_partition, progress.flash_size)
+    }
 
+    /// Calculates crc of first `len` bytes of OTA partition
+    fn calc_partition_crc(&mut self, partition: usize, len: u32) -> Result<u32> {
         let mut calc_crc = 0;
         let mut bytes = [0; OTA_VERIFY_READ_SIZE];
 
-        let mut partition_offset = self.pinfo.ota_partitions[progress.target_partition].0;
-        let mut remaining = progress.flash_size;
+        let mut partition_offset = self.pinfo.ota_partitions[partition].0;
+        let mut remaining = len;
 
         loop {
             let n = remaining.min(OTA_VERIFY_READ_SIZE as u32);
@@ -180,19 +707,27 @@ where
                 break;
             }
 
-            _ = self.flash.read(partition_offset, &mut bytes[..n as usize]);
+            self.flash
+                .read(partition_offset, &mut bytes[..n as usize])
+                .map_err(|_| OtaError::FlashReadError {
+                    offset: partition_offset,
+                })?;
             partition_offset += n;
             remaining -= n;
 
             calc_crc = crc32::calc_crc32(&bytes[..n as usize], calc_crc);
         }
 
-        Ok(calc_crc == progress.target_crc)
+        Ok(calc_crc)
     }
 
     /// Sets ota boot target partition
-    pub fn set_target_ota_boot_partition(&mut self, target: usize, state: OtaImgState) {
-        let (slot1, slot2) = self.get_ota_boot_entries();
+    pub fn set_target_ota_boot_partition(
+        &mut self,
+        target: usize,
+        state: OtaImgState,
+    ) -> Result<()> {
+        let (slot1, slot2) = self.get_ota_boot_entries()?;
         let (seq1, seq2) = (slot1.seq, slot2.seq);
 
         let mut target_seq = seq1.max(seq2);
@@ -202,80 +737,143 @@ where
             target_seq += 1;
         }
 
-        let flash = &mut self.flash;
-        let target_crc = crc32::calc_crc32(&target_seq.to_le_bytes(), 0xFFFFFFFF);
-        if seq1 > seq2 {
-            let offset = self.pinfo.otadata_offset + (self.pinfo.otadata_size >> 1);
-
-            _ = flash.write(offset, &target_seq.to_le_bytes());
-            _ = flash.write(offset + 32 - 4 - 4, &(state as u32).to_le_bytes());
-            _ = flash.write(offset + 32 - 4, &target_crc.to_le_bytes());
+        let (offset, mut entry) = if seq1 > seq2 {
+            (
+                self.pinfo.otadata_offset + (self.pinfo.otadata_size >> 1),
+                slot2,
+            )
         } else {
-            _ = flash.write(self.pinfo.otadata_offset, &target_seq.to_le_bytes());
-            _ = flash.write(
-                self.pinfo.otadata_offset + 32 - 4 - 4,
-                &(state as u32).to_le_bytes(),
-            );
-            _ = flash.write(
-                self.pinfo.otadata_offset + 32 - 4,
-                &target_crc.to_le_bytes(),
-            );
-        }
+            (self.pinfo.otadata_offset, slot1)
+        };
+
+        entry.seq = target_seq;
+        entry.seq_label = [0xFF; 20];
+        entry.ota_state = state;
+        entry.crc = crc32::calc_crc32(&target_seq.to_le_bytes(), 0xFFFFFFFF);
+        self.write_flash(offset, &entry.to_bytes())
     }
 
     pub fn set_ota_state(&mut self, slot: u8, state: OtaImgState) -> Result<()> {
-        let offset = match slot {
-            1 => self.pinfo.otadata_offset,
-            2 => self.pinfo.otadata_offset + (self.pinfo.otadata_size >> 1),
+        let offset = self.get_otadata_entry_offset(slot)?;
+        self.write_flash(offset + 32 - 4 - 4, &u32::from(state).to_le_bytes())
+    }
+
+    /// Returns flash offset of otadata slot (1 or 2)
+    fn get_otadata_entry_offset(&self, slot: u8) -> Result<u32> {
+        match slot {
+            1 => Ok(self.pinfo.otadata_offset),
+            2 => Ok(self.pinfo.otadata_offset + (self.pinfo.otadata_size >> 1)),
             _ => {
                 error!("Use slot1 or slot2!");
-                return Err(OtaError::CannotFindCurrentBootPartition);
+                Err(OtaError::InvalidOtaDataSlot { slot })
             }
-        };
-
-        _ = self
-            .flash
-            .write(offset + 32 - 4 - 4, &(state as u32).to_le_bytes());
-
-        Ok(())
+        }
     }
 
     /// Returns current OTA boot sequences
     ///
     /// NOTE: if crc doesn't match, it returns 0 for that seq
-    /// NOTE: [Entry struct (link to .h file)](https://github.com/espressif/esp-idf/blob/master/components/bootloader_support/include/esp_flash_partitions.h#L66)
-    pub fn get_ota_boot_entries(&mut self) -> (EspOtaSelectEntry, EspOtaSelectEntry) {
-        let mut bytes = [0; 32];
-        _ = self.flash.read(self.pinfo.otadata_offset, &mut bytes);
-        let mut slot1: EspOtaSelectEntry =
-            unsafe { core::ptr::read(bytes.as_ptr() as *const EspOtaSelectEntry) };
+    pub fn get_ota_boot_entries(&mut self) -> Result<(EspOtaSelectEntry, EspOtaSelectEntry)> {
+        let mut bytes = [0; EspOtaSelectEntry::SIZE];
+        self.read_flash(self.pinfo.otadata_offset, &mut bytes)?;
+        let mut slot1 = EspOtaSelectEntry::from_bytes(&bytes);
         slot1.check_crc();
 
-        _ = self.flash.read(
+        self.read_flash(
             self.pinfo.otadata_offset + (self.pinfo.otadata_size >> 1),
             &mut bytes,
-        );
-        let mut slot2: EspOtaSelectEntry =
-            unsafe { core::ptr::read(bytes.as_ptr() as *const EspOtaSelectEntry) };
+        )?;
+        let mut slot2 = EspOtaSelectEntry::from_bytes(&bytes);
         slot2.check_crc();
 
-        (slot1, slot2)
+        Ok((slot1, slot2))
     }
 
     /// Returns currently booted partition index
     pub fn get_currently_booted_partition(&self) -> Option<usize> {
-        mmu_hal::esp_get_current_running_partition(self.get_partitions())
+        self.running.map(|(partition, _)| partition)
+    }
+
+    /// Returns currently booted partition index and the way it was detected
+    pub fn get_running_partition(&self) -> Option<(usize, BootPartitionSource)> {
+        self.running
     }
 
     /// BUG: this wont work if user has ota partitions not starting from ota0
     /// or if user skips some ota partitions: ota0, ota2, ota3...
     pub fn get_next_ota_partition(&self) -> Option<usize> {
-        let curr_part = mmu_hal::esp_get_current_running_partition(self.get_partitions());
+        let curr_part = self.get_currently_booted_partition();
         curr_part.map(|next_part| (next_part + 1) % self.pinfo.ota_partitions_count)
     }
 
+    /// Detects running partition using flash MMU. If address translation fails (or no chip
+    /// feature is selected), uses the otadata entry bootloader selects (highest seq, not
+    /// marked invalid or aborted). Code running outside of OTA partitions (factory app)
+    /// has no running partition.
+    fn detect_running_partition(&mut self) -> Result<Option<(usize, BootPartitionSource)>> {
+        #[cfg(has_mmu)]
+        match mmu_hal::esp_get_current_running_partition(self.get_partitions()) {
+            mmu_hal::RunningPartition::Found(partition) => {
+                return Ok(Some((partition, BootPartitionSource::Mmu)));
+            }
+            mmu_hal::RunningPartition::NotFound => {
+                warn!("[OTA] Running code is outside of OTA partitions (factory app?)");
+                return Ok(None);
+            }
+            mmu_hal::RunningPartition::Unknown => {}
+        }
+
+        let Some(partition) = self.get_boot_partition()? else {
+            warn!("[OTA] Cannot detect running partition (factory app?)");
+            return Ok(None);
+        };
+
+        warn!(
+            "[OTA] MMU lookup failed, running partition {} taken from otadata",
+            partition
+        );
+        Ok(Some((partition, BootPartitionSource::Otadata)))
+    }
+
+    /// Returns OTA partition that bootloader selects from otadata (valid entry with
+    /// highest seq, pointing to partition with image)
+    fn get_boot_partition(&mut self) -> Result<Option<usize>> {
+        let (slot1, slot2) = self.get_ota_boot_entries()?;
+        let Some(entry) = [slot1, slot2]
+            .into_iter()
+            .filter(|entry| {
+                entry.seq != 0
+                    && !matches!(
+                        entry.ota_state,
+                        OtaImgState::EspOtaImgInvalid | OtaImgState::EspOtaImgAborted
+                    )
+            })
+            .max_by_key(|entry| entry.seq)
+        else {
+            return Ok(None);
+        };
+
+        // bootloader falls back to other image if selected one is missing
+        let partition = helpers::seq_to_part(entry.seq, self.pinfo.ota_partitions_count);
+        if !self.is_image_present(partition)? {
+            return Ok(None);
+        }
+
+        Ok(Some(partition))
+    }
+
+    /// Returns otadata slot (1 or 2) with highest seq, regardless of its state
+    fn get_latest_slot(&mut self) -> Result<Option<(u8, EspOtaSelectEntry)>> {
+        let (slot1, slot2) = self.get_ota_boot_entries()?;
+        Ok(match (slot1.seq, slot2.seq) {
+            (0, 0) => None,
+            (seq1, seq2) if seq1 >= seq2 => Some((1, slot1)),
+            _ => Some((2, slot2)),
+        })
+    }
+
     fn get_current_slot(&mut self) -> Result<(u8, EspOtaSelectEntry)> {
-        let (slot1, slot2) = self.get_ota_boot_entries();
+        let (slot1, slot2) = self.get_ota_boot_entries()?;
         let current_partition = self
             .get_currently_booted_partition()
             .ok_or(OtaError::CannotFindCurrentBootPartition)?;
@@ -292,7 +890,7 @@ where
     }
 
     pub fn get_ota_image_state(&mut self) -> Result<OtaImgState> {
-        let (slot1, slot2) = self.get_ota_boot_entries();
+        let (slot1, slot2) = self.get_ota_boot_entries()?;
         let current_partition = self
             .get_currently_booted_partition()
             .ok_or(OtaError::CannotFindCurrentBootPartition)?;
@@ -330,51 +928,159 @@ where
         Ok(())
     }
 
+    /// Rolls back to previous image
+    ///
+    /// Picks other (not running) partition referenced by otadata with highest seq and
+    /// [`OtaImgState::EspOtaImgValid`] or [`OtaImgState::EspOtaImgUndefined`] state,
+    /// sets it as boot partition and marks currently running image as invalid.
+    /// Unlike [`Ota::ota_mark_app_invalid_rollback`] this also works if running app
+    /// was already marked valid.
+    ///
+    /// Returns index of partition that will boot after reset
+    pub fn ota_rollback(&mut self) -> Result<usize> {
+        let current_partition = self
+            .get_currently_booted_partition()
+            .ok_or(OtaError::CannotFindCurrentBootPartition)?;
+
+        let (slot1, slot2) = self.get_ota_boot_entries()?;
+        let partitions_count = self.pinfo.ota_partitions_count;
+        let Some(target) = [slot1, slot2]
+            .into_iter()
+            .filter(|entry| {
+                entry.seq != 0
+                    && helpers::seq_to_part(entry.seq, partitions_count) != current_partition
+                    && matches!(
+                        entry.ota_state,
+                        OtaImgState::EspOtaImgValid | OtaImgState::EspOtaImgUndefined
+                    )
+            })
+            .max_by_key(|entry| entry.seq)
+        else {
+            error!("[OTA] No valid image to rollback to!");
+
+            return Err(OtaError::NoRollbackTarget);
+        };
+
+        let target_partition = helpers::seq_to_part(target.seq, partitions_count);
+        if !self.is_image_present(target_partition)? {
+            error!("[OTA] Rollback partition doesn't contain app image!");
+
+            return Err(OtaError::NoRollbackTarget);
+        }
+
+        // entry of running image could be overwritten by the new one (if it had lower seq)
+        self.set_target_ota_boot_partition(target_partition, target.ota_state)?;
+        if let Ok((slot, _)) = self.get_current_slot() {
+            self.set_ota_state(slot, OtaImgState::EspOtaImgInvalid)?;
+        }
+
+        info!("[OTA] Rolled back to partition {}", target_partition);
+        Ok(target_partition)
+    }
+
+    /// Checks if partition starts with app image header magic
+    fn is_image_present(&mut self, partition: usize) -> Result<bool> {
+        let mut magic = [0; 1];
+        self.read_flash(self.pinfo.ota_partitions[partition].0, &mut magic)?;
+
+        Ok(magic[0] == EspImageHeader::MAGIC)
+    }
+
+    fn read_flash(&mut self, offset: u32, bytes: &mut [u8]) -> Result<()> {
+        self.flash
+            .read(offset, bytes)
+            .map_err(|_| OtaError::FlashReadError { offset })
+    }
+
+    fn write_flash(&mut self, offset: u32, bytes: &[u8]) -> Result<()> {
+        self.flash
+            .write(offset, bytes)
+            .map_err(|_| OtaError::FlashWriteError { offset })
+    }
+
+    /// Erases `len` bytes rounded up to whole sectors by writing 0xFF through `Storage`
+    /// (read-modify-write, not a `NorFlash` erase)
+    ///
+    /// Sectors that are already erased are skipped
+    fn erase_flash(&mut self, offset: u32, len: u32) -> Result<()> {
+        let erased = [0xFF; OTA_BLOCK_SIZE as usize];
+        for sector in 0..BlockBitmap::blocks_for(len) {
+            let sector_offset = offset + sector * OTA_BLOCK_SIZE;
+            if !self.is_erased(sector_offset, OTA_BLOCK_SIZE)? {
+                self.write_flash(sector_offset, &erased)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_erased(&mut self, offset: u32, len: u32) -> Result<bool> {
+        let mut bytes = [0; OTA_VERIFY_READ_SIZE];
+        for pos in (0..len).step_by(OTA_VERIFY_READ_SIZE) {
+            let n = (len - pos).min(OTA_VERIFY_READ_SIZE as u32) as usize;
+            self.read_flash(offset + pos, &mut bytes[..n])?;
+            if bytes[..n].iter().any(|&b| b != 0xFF) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
     fn read_partitions(flash: &mut S) -> Result<PartitionInfo> {
         let mut tmp_pinfo = PartitionInfo {
             ota_partitions: [(0, 0); 16],
             ota_partitions_count: 0,
+            ota_labels: [[0; 16]; 16],
             otadata_size: 0,
             otadata_offset: 0,
         };
 
-        let mut bytes = [0xFF; 32];
+        let mut bytes = [0xFF; PartitionEntry::SIZE];
         let mut last_ota_part: i8 = -1;
-        for read_offset in (0..PART_SIZE).step_by(32) {
-            _ = flash.read(PART_OFFSET + read_offset, &mut bytes);
-            if bytes == [0xFF; 32] {
+        for read_offset in (0..PART_SIZE).step_by(PartitionEntry::SIZE) {
+            flash
+                .read(PART_OFFSET + read_offset, &mut bytes)
+                .map_err(|_| OtaError::FlashReadError {
+                    offset: PART_OFFSET + read_offset,
+                })?;
+            if bytes == [0xFF; PartitionEntry::SIZE] {
                 break;
             }
 
-            let magic = &bytes[0..2];
-            if magic != [0xAA, 0x50] {
+            let Some(entry) = PartitionEntry::from_bytes(&bytes) else {
                 continue;
-            }
-
-            let p_type = &bytes[2];
-            let p_subtype = &bytes[3];
-            let p_offset = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
-            let p_size = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
-            //let p_name = core::str::from_utf8(&bytes[12..28]).unwrap();
-            //let p_flags = u32::from_le_bytes(bytes[28..32].try_into().unwrap());
-            //log::info!("{magic:?} {p_type} {p_subtype} {p_offset} {p_size} {p_name} {p_flags}");
+            };
 
-            if *p_type == 0 && *p_subtype >= FIRST_OTA_PART_SUBTYPE {
-                let ota_part_idx = *p_subtype - FIRST_OTA_PART_SUBTYPE;
+            if entry.p_type == 0 && entry.p_subtype >= FIRST_OTA_PART_SUBTYPE {
+                let ota_part_idx = entry.p_subtype - FIRST_OTA_PART_SUBTYPE;
                 if ota_part_idx as i8 - last_ota_part != 1 {
-                    return Err(OtaError::WrongOTAPArtitionOrder);
+                    return Err(OtaError::WrongOTAPArtitionOrder {
+                        expected: (last_ota_part + 1) as usize,
+                        found: ota_part_idx as usize,
+                    });
                 }
 
                 last_ota_part = ota_part_idx as i8;
-                tmp_pinfo.ota_partitions[tmp_pinfo.ota_partitions_count] = (p_offset, p_size);
+                tmp_pinfo.ota_partitions[tmp_pinfo.ota_partitions_count] =
+                    (entry.offset, entry.size);
+                tmp_pinfo.ota_labels[tmp_pinfo.ota_partitions_count] = entry.label;
                 tmp_pinfo.ota_partitions_count += 1;
-            } else if *p_type == 1 && *p_subtype == 0 {
+            } else if entry.p_type == 1 && entry.p_subtype == 0 {
                 //otadata
-                tmp_pinfo.otadata_offset = p_offset;
-                tmp_pinfo.otadata_size = p_size;
+                tmp_pinfo.otadata_offset = entry.offset;
+                tmp_pinfo.otadata_size = entry.size;
             }
         }
 
         Ok(tmp_pinfo)
     }
 }
+
+/// State of newly activated image
+fn img_state(rollback: bool) -> OtaImgState {
+    match rollback {
+        true => OtaImgState::EspOtaImgNew,
+        false => OtaImgState::EspOtaImgUndefined,
+    }
+}
@@ -3,48 +3,130 @@ pub const MMU_PAGE_16KB: u32 = 0x4000;
 pub const MMU_PAGE_32KB: u32 = 0x8000;
 pub const MMU_PAGE_64KB: u32 = 0x10000;
 
-fn mmu_hal_pages_to_bytes(mmu_id: u32, page_num: u32) -> u32 {
-    let mmu_page_size = crate::mmu_ll::mmu_ll_get_page_size(mmu_id);
-    let shift_code = match mmu_page_size {
-        MMU_PAGE_64KB => 16,
-        MMU_PAGE_32KB => 15,
-        MMU_PAGE_16KB => 14,
-        _ => panic!("WRONG MMU_PAGE_SIZE! 0x{:X?}", mmu_page_size),
-    };
+/// Max number of MMUs (chips with one MMU for all external memory only use id 0)
+pub const MMU_MAX_ID: usize = 2;
+
+/// Memory that MMU entry maps to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MmuTarget {
+    Flash,
+    Psram,
+}
+
+/// Access to MMU registers
+///
+/// Implemented by `mmu_ll::HwMmuTable` for selected chip, and by [`MockMmuTable`]
+/// so chip specific decoding in [`crate::mmu_ll`] can be tested on host
+pub trait MmuTable {
+    /// Returns raw MMU entry (as stored in MMU table)
+    fn read_entry(&self, mmu_id: u32, entry_id: u32) -> u32;
+
+    /// Returns raw page size config field (ignored by chips with fixed page size)
+    fn page_size_code(&self, mmu_id: u32) -> u32;
+}
+
+/// MMU table kept in memory
+#[derive(Debug, Clone)]
+pub struct MockMmuTable<const N: usize> {
+    pub entries: [[u32; N]; MMU_MAX_ID],
+    pub page_size_code: [u32; MMU_MAX_ID],
+}
+
+impl<const N: usize> MockMmuTable<N> {
+    /// Creates table with all entries set to `raw` (use chip invalid value)
+    pub const fn new(raw: u32) -> Self {
+        Self {
+            entries: [[raw; N]; MMU_MAX_ID],
+            page_size_code: [0; MMU_MAX_ID],
+        }
+    }
 
-    page_num << shift_code
+    pub fn set_entry(&mut self, mmu_id: u32, entry_id: u32, raw: u32) {
+        self.entries[mmu_id as usize][entry_id as usize] = raw;
+    }
 }
 
-pub fn esp_get_current_running_partition(partitions: &[(u32, u32)]) -> Option<usize> {
+impl<const N: usize> MmuTable for MockMmuTable<N> {
+    fn read_entry(&self, mmu_id: u32, entry_id: u32) -> u32 {
+        self.entries[mmu_id as usize][entry_id as usize]
+    }
+
+    fn page_size_code(&self, mmu_id: u32) -> u32 {
+        self.page_size_code[mmu_id as usize]
+    }
+}
+
+/// Translates cached virtual address (of code, rodata or anything else mapped from flash)
+/// to physical flash address (like `mmu_hal_vaddr_to_paddr` in ESP-IDF)
+///
+/// Returns `None` if `vaddr` isn't in cached external memory region, its MMU entry
+/// is invalid or it's mapped to PSRAM. Only available with chip feature selected.
+#[cfg(has_mmu)]
+pub fn vaddr_to_paddr(vaddr: u32) -> Option<u32> {
+    use crate::mmu_ll::MmuLl;
+
     // NOTE:
-    // mmu_id is always 0 because s_vaddr_to_paddr is using 0 for all targets
-    // except esp32p4 (per SOC_MMU_PER_EXT_MEM_TARGET define)
+    // mmu_id is 0 for all targets except esp32p4, which has separate flash and PSRAM MMUs
+    // (per SOC_MMU_PER_EXT_MEM_TARGET define)
     //
     // https://github.com/espressif/esp-idf/blob/b5ac4fbdf9e9fb320bb0a98ee4fbaa18f8566f37/components/esp_mm/esp_mmu_map.c#L754
-    let mmu_id = 0;
+    let mmu_id = crate::mmu_ll::Chip::get_mmu_id(vaddr);
 
-    let ptr = esp_get_current_running_partition as *const () as *const u32;
-    let entry_id = crate::mmu_ll::mmu_ll_get_entry_id(mmu_id, ptr as u32);
+    crate::mmu_ll::Chip::vaddr_to_paddr(&crate::mmu_ll::HwMmuTable, mmu_id, vaddr)
+}
 
-    if !crate::mmu_ll::mmu_ll_check_entry_valid(mmu_id, entry_id) {
-        error!("mmu_ll_check_entry_valid failed!");
+/// Chip independent part of `vaddr_to_paddr`, MMU is accessed through given functions
+///
+/// `entry_id` - returns MMU entry id of vaddr (already checked to be in cached region)
+/// `flash_paddr_base` - returns physical address of page mapped by entry, or `None` if
+/// entry is invalid or doesn't map flash
+pub fn vaddr_to_paddr_with(
+    vaddr: u32,
+    page_size: u32,
+    entry_id: impl FnOnce(u32) -> u32,
+    flash_paddr_base: impl FnOnce(u32) -> Option<u32>,
+) -> Option<u32> {
+    if !matches!(
+        page_size,
+        MMU_PAGE_8KB | MMU_PAGE_16KB | MMU_PAGE_32KB | MMU_PAGE_64KB
+    ) {
+        error!("Wrong MMU page size! 0x{:X}", page_size);
 
         return None;
     }
 
     // page_num is always 1
     // https://github.com/espressif/esp-idf/blob/master/components/hal/mmu_hal.c#L129
-    let page_size_in_bytes = mmu_hal_pages_to_bytes(mmu_id, 1);
-    let offset = (ptr as u32) % page_size_in_bytes;
+    let offset = vaddr % page_size;
+    let paddr_base = flash_paddr_base(entry_id(vaddr))?;
 
-    let paddr_base = crate::mmu_ll::mmu_ll_entry_id_to_paddr_base(mmu_id, entry_id);
-    let paddr = paddr_base | offset;
+    Some(paddr_base | offset)
+}
 
-    for (i, part) in partitions.iter().enumerate() {
-        if paddr >= part.0 && paddr < part.0 + part.1 {
-            return Some(i);
-        }
-    }
+/// Result of [`esp_get_current_running_partition`]
+#[cfg(has_mmu)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunningPartition {
+    /// Running code is in partition with given index
+    Found(usize),
+    /// Running code is outside of given partitions (e.g. factory app)
+    NotFound,
+    /// Address of running code cannot be translated
+    Unknown,
+}
+
+/// Looks up partition (from `partitions` list of `(offset, size)`) that contains running code
+#[cfg(has_mmu)]
+pub fn esp_get_current_running_partition(partitions: &[(u32, u32)]) -> RunningPartition {
+    let ptr = esp_get_current_running_partition as *const () as *const u32;
+    let Some(paddr) = vaddr_to_paddr(ptr as u32) else {
+        error!("Cannot translate address of running code!");
+        return RunningPartition::Unknown;
+    };
 
-    None
+    partitions
+        .iter()
+        .position(|part| paddr >= part.0 && paddr < part.0 + part.1)
+        .map_or(RunningPartition::NotFound, RunningPartition::Found)
 }
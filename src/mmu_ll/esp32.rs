@@ -1,78 +1,119 @@
+use super::MmuLl;
+use crate::mmu_hal::{MMU_PAGE_64KB, MmuTable, MmuTarget};
+use core::ops::Range;
+
 const SOC_MMU_VADDR_MASK: u32 = 0x3FFFFF;
 const SOC_MMU_INVALID: u32 = 1 << 8;
 const MMU_LL_PSRAM_ENTRY_START_ID: u32 = 1152;
-const SOC_IRAM0_CACHE_ADDRESS_LOW: u32 = 0x400D0000;
-const SOC_IRAM0_CACHE_ADDRESS_HIGH: u32 = 0x40400000;
-const SOC_IRAM1_CACHE_ADDRESS_LOW: u32 = 0x40400000;
-const SOC_IRAM1_CACHE_ADDRESS_HIGH: u32 = 0x40800000;
-const SOC_IROM0_CACHE_ADDRESS_LOW: u32 = 0x40800000;
-const SOC_IROM0_CACHE_ADDRESS_HIGH: u32 = 0x40C00000;
-const SOC_DRAM1_CACHE_ADDRESS_LOW: u32 = 0x3F800000;
-const SOC_DRAM1_CACHE_ADDRESS_HIGH: u32 = 0x3FC00000;
-const SOC_DROM0_CACHE_ADDRESS_LOW: u32 = 0x3F400000;
-const SOC_DROM0_CACHE_ADDRESS_HIGH: u32 = 0x3F800000;
-const DPORT_PRO_FLASH_MMU_TABLE: u32 = 0x3FF10000;
+const SOC_IRAM0_CACHE: Range<u32> = 0x400D0000..0x40400000;
+const SOC_IRAM1_CACHE: Range<u32> = 0x40400000..0x40800000;
+const SOC_IROM0_CACHE: Range<u32> = 0x40800000..0x40C00000;
+const SOC_DRAM1_CACHE: Range<u32> = 0x3F800000..0x3FC00000;
+const SOC_DROM0_CACHE: Range<u32> = 0x3F400000..0x3F800000;
 
-pub fn mmu_ll_get_page_size(_mmu_id: u32) -> u32 {
-    crate::mmu_hal::MMU_PAGE_64KB
-}
+pub struct Esp32;
 
-pub fn mmu_ll_get_entry_id(_mmu_id: u32, vaddr: u32) -> u32 {
-    let mut offset = 0;
-    let mut shift_code = 0;
-    let mut vaddr_mask = 0;
-
-    if soc_address_in_bus!(SOC_DROM0_CACHE, vaddr) {
-        offset = 0;
-        shift_code = 16;
-        vaddr_mask = SOC_MMU_VADDR_MASK;
-    } else if soc_address_in_bus!(SOC_IRAM0_CACHE, vaddr) {
-        offset = 64;
-        shift_code = 16;
-        vaddr_mask = SOC_MMU_VADDR_MASK;
-    } else if soc_address_in_bus!(SOC_IRAM1_CACHE, vaddr) {
-        offset = 128;
-        shift_code = 16;
-        vaddr_mask = SOC_MMU_VADDR_MASK;
-    } else if soc_address_in_bus!(SOC_IROM0_CACHE, vaddr) {
-        offset = 192;
-        shift_code = 16;
-        vaddr_mask = SOC_MMU_VADDR_MASK;
-    } else if soc_address_in_bus!(SOC_DRAM1_CACHE, vaddr) {
-        offset = MMU_LL_PSRAM_ENTRY_START_ID;
-        shift_code = 15;
-        vaddr_mask = SOC_MMU_VADDR_MASK >> 1;
-    } else {
-        error!("mmu_ll_get_entry_id failed!");
+impl MmuLl for Esp32 {
+    fn get_page_size(_mmu: &impl MmuTable, _mmu_id: u32) -> u32 {
+        MMU_PAGE_64KB
     }
 
-    offset + ((vaddr & vaddr_mask) >> shift_code)
-}
+    fn check_valid_ext_vaddr_region(_mmu: &impl MmuTable, _mmu_id: u32, vaddr: u32) -> bool {
+        [
+            SOC_DROM0_CACHE,
+            SOC_IRAM0_CACHE,
+            SOC_IRAM1_CACHE,
+            SOC_IROM0_CACHE,
+            SOC_DRAM1_CACHE,
+        ]
+        .iter()
+        .any(|bus| bus.contains(&vaddr))
+    }
 
-pub fn mmu_ll_entry_id_to_paddr_base(_mmu_id: u32, entry_id: u32) -> u32 {
-    let level = unsafe { dport_interrupt_disable() };
-    let mmu_val = unsafe { *(DPORT_PRO_FLASH_MMU_TABLE as *const u32).offset(entry_id as isize) };
-    unsafe { dport_interrupt_restore(level) };
+    fn get_entry_id(_mmu: &impl MmuTable, _mmu_id: u32, vaddr: u32) -> u32 {
+        let mut offset = 0;
+        let mut shift_code = 0;
+        let mut vaddr_mask = 0;
+
+        if SOC_DROM0_CACHE.contains(&vaddr) {
+            offset = 0;
+            shift_code = 16;
+            vaddr_mask = SOC_MMU_VADDR_MASK;
+        } else if SOC_IRAM0_CACHE.contains(&vaddr) {
+            offset = 64;
+            shift_code = 16;
+            vaddr_mask = SOC_MMU_VADDR_MASK;
+        } else if SOC_IRAM1_CACHE.contains(&vaddr) {
+            offset = 128;
+            shift_code = 16;
+            vaddr_mask = SOC_MMU_VADDR_MASK;
+        } else if SOC_IROM0_CACHE.contains(&vaddr) {
+            offset = 192;
+            shift_code = 16;
+            vaddr_mask = SOC_MMU_VADDR_MASK;
+        } else if SOC_DRAM1_CACHE.contains(&vaddr) {
+            offset = MMU_LL_PSRAM_ENTRY_START_ID;
+            shift_code = 15;
+            vaddr_mask = SOC_MMU_VADDR_MASK >> 1;
+        } else {
+            error!("mmu_ll_get_entry_id failed!");
+        }
+
+        offset + ((vaddr & vaddr_mask) >> shift_code)
+    }
+
+    fn entry_id_to_paddr_base(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> u32 {
+        let mmu_val = mmu.read_entry(mmu_id, entry_id);
+        if entry_id >= MMU_LL_PSRAM_ENTRY_START_ID {
+            mmu_val << 15
+        } else {
+            mmu_val << 16
+        }
+    }
 
-    if entry_id >= MMU_LL_PSRAM_ENTRY_START_ID {
-        mmu_val << 15
-    } else {
-        mmu_val << 16
+    fn check_entry_valid(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> bool {
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_INVALID) == 0
+    }
+
+    fn get_entry_target(_mmu: &impl MmuTable, _mmu_id: u32, entry_id: u32) -> MmuTarget {
+        if entry_id >= MMU_LL_PSRAM_ENTRY_START_ID {
+            MmuTarget::Psram
+        } else {
+            MmuTarget::Flash
+        }
     }
 }
 
-pub fn mmu_ll_check_entry_valid(_mmu_id: u32, entry_id: u32) -> bool {
-    let level = unsafe { dport_interrupt_disable() };
-    let mmu_val = unsafe { *(DPORT_PRO_FLASH_MMU_TABLE as *const u32).offset(entry_id as isize) };
-    unsafe { dport_interrupt_restore(level) };
+#[cfg(feature = "esp32")]
+const DPORT_PRO_FLASH_MMU_TABLE: u32 = 0x3FF10000;
+
+/// MMU registers of esp32
+#[cfg(feature = "esp32")]
+pub struct HwMmuTable;
+
+#[cfg(feature = "esp32")]
+impl MmuTable for HwMmuTable {
+    fn read_entry(&self, _mmu_id: u32, entry_id: u32) -> u32 {
+        let level = unsafe { dport_interrupt_disable() };
+        let mmu_val =
+            unsafe { *(DPORT_PRO_FLASH_MMU_TABLE as *const u32).offset(entry_id as isize) };
+        unsafe { dport_interrupt_restore(level) };
 
-    (mmu_val & SOC_MMU_INVALID) == 0
+        mmu_val
+    }
+
+    fn page_size_code(&self, _mmu_id: u32) -> u32 {
+        0
+    }
 }
 
 // NOTE:Idk if required!
 // Only used when using second core etc.
 
+#[cfg(feature = "esp32")]
 const SOC_DPORT_WORKAROUND_DIS_INTERRUPT_LVL: u32 = 5;
+
+#[cfg(feature = "esp32")]
 #[inline(always)]
 unsafe fn dport_interrupt_disable() -> u32 {
     let level: u32;
@@ -89,6 +130,7 @@ unsafe fn dport_interrupt_disable() -> u32 {
     level
 }
 
+#[cfg(feature = "esp32")]
 #[inline(always)]
 unsafe fn dport_interrupt_restore(level: u32) {
     unsafe {
@@ -1,55 +1,71 @@
-use crate::mmu_hal::{MMU_PAGE_16KB, MMU_PAGE_32KB, MMU_PAGE_64KB};
+use super::{MmuLl, page_shift};
+use crate::mmu_hal::{MMU_PAGE_16KB, MMU_PAGE_32KB, MMU_PAGE_64KB, MmuTable, MmuTarget};
 
-const DR_REG_MMU_TABLE: u32 = 0x600c5000;
 const SOC_MMU_VALID_VAL_MASK: u32 = 0x3f;
 const SOC_MMU_INVALID: u32 = 1 << 6;
+const SOC_MMU_ENTRY_NUM: u32 = 64;
+const SOC_IRAM0_CACHE_ADDRESS_LOW: u32 = 0x42000000;
+const SOC_DRAM0_CACHE_ADDRESS_LOW: u32 = 0x3C000000;
 
-fn soc_mmu_vaddr_mask(mmu_id: u32) -> u32 {
-    mmu_ll_get_page_size(mmu_id) * 64 - 1
-}
+pub struct Esp32c2;
 
-pub fn mmu_ll_get_page_size(_mmu_id: u32) -> u32 {
-    let extmem = unsafe { &*esp32c2::EXTMEM::ptr() };
-    let cache_mmu_page_size = extmem.cache_conf_misc().read().cache_mmu_page_size().bits();
-    match cache_mmu_page_size {
-        0 => MMU_PAGE_16KB,
-        1 => MMU_PAGE_32KB,
-        _ => MMU_PAGE_64KB,
+impl Esp32c2 {
+    fn soc_mmu_vaddr_mask(mmu: &impl MmuTable, mmu_id: u32) -> u32 {
+        Self::get_page_size(mmu, mmu_id) * SOC_MMU_ENTRY_NUM - 1
     }
 }
 
-pub fn mmu_ll_get_entry_id(mmu_id: u32, vaddr: u32) -> u32 {
-    let shift_code = match mmu_ll_get_page_size(mmu_id) {
-        MMU_PAGE_64KB => 16,
-        MMU_PAGE_32KB => 15,
-        MMU_PAGE_16KB => 14,
-        _ => {
-            error!("mmu_ll_get_entry_id failed!");
-
-            0
+impl MmuLl for Esp32c2 {
+    fn get_page_size(mmu: &impl MmuTable, mmu_id: u32) -> u32 {
+        match mmu.page_size_code(mmu_id) {
+            0 => MMU_PAGE_16KB,
+            1 => MMU_PAGE_32KB,
+            _ => MMU_PAGE_64KB,
         }
-    };
+    }
 
-    (vaddr & soc_mmu_vaddr_mask(mmu_id)) >> shift_code
-}
+    fn check_valid_ext_vaddr_region(mmu: &impl MmuTable, mmu_id: u32, vaddr: u32) -> bool {
+        let size = Self::get_page_size(mmu, mmu_id) * SOC_MMU_ENTRY_NUM;
 
-pub fn mmu_ll_entry_id_to_paddr_base(mmu_id: u32, entry_id: u32) -> u32 {
-    let shift_code = match mmu_ll_get_page_size(mmu_id) {
-        MMU_PAGE_64KB => 16,
-        MMU_PAGE_32KB => 15,
-        MMU_PAGE_16KB => 14,
-        _ => {
-            error!("mmu_ll_entry_id_to_paddr_base failed!");
+        (SOC_IRAM0_CACHE_ADDRESS_LOW..SOC_IRAM0_CACHE_ADDRESS_LOW + size).contains(&vaddr)
+            || (SOC_DRAM0_CACHE_ADDRESS_LOW..SOC_DRAM0_CACHE_ADDRESS_LOW + size).contains(&vaddr)
+    }
 
-            0
-        }
-    };
+    fn get_entry_id(mmu: &impl MmuTable, mmu_id: u32, vaddr: u32) -> u32 {
+        let shift_code = page_shift(Self::get_page_size(mmu, mmu_id));
+        (vaddr & Self::soc_mmu_vaddr_mask(mmu, mmu_id)) >> shift_code
+    }
+
+    fn entry_id_to_paddr_base(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> u32 {
+        let shift_code = page_shift(Self::get_page_size(mmu, mmu_id));
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_VALID_VAL_MASK) << shift_code
+    }
 
-    let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
-    unsafe { ((*ptr) & SOC_MMU_VALID_VAL_MASK) << shift_code }
+    fn check_entry_valid(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> bool {
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_INVALID) == 0
+    }
+
+    fn get_entry_target(_mmu: &impl MmuTable, _mmu_id: u32, _entry_id: u32) -> MmuTarget {
+        MmuTarget::Flash
+    }
 }
 
-pub fn mmu_ll_check_entry_valid(_mmu_id: u32, entry_id: u32) -> bool {
-    let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
-    unsafe { ((*ptr) & SOC_MMU_INVALID) == 0 }
+#[cfg(feature = "esp32c2")]
+const DR_REG_MMU_TABLE: u32 = 0x600c5000;
+
+/// MMU registers of esp32c2
+#[cfg(feature = "esp32c2")]
+pub struct HwMmuTable;
+
+#[cfg(feature = "esp32c2")]
+impl MmuTable for HwMmuTable {
+    fn read_entry(&self, _mmu_id: u32, entry_id: u32) -> u32 {
+        let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
+        unsafe { *ptr }
+    }
+
+    fn page_size_code(&self, _mmu_id: u32) -> u32 {
+        let extmem = unsafe { &*esp32c2::EXTMEM::ptr() };
+        extmem.cache_conf_misc().read().cache_mmu_page_size().bits() as u32
+    }
 }
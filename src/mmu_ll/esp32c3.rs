@@ -1,22 +1,56 @@
+use super::MmuLl;
+use crate::mmu_hal::{MMU_PAGE_64KB, MmuTable, MmuTarget};
+use core::ops::Range;
+
 const SOC_MMU_VADDR_MASK: u32 = 0x7FFFFF;
-const DR_REG_MMU_TABLE: u32 = 0x600c5000;
 const SOC_MMU_VALID_VAL_MASK: u32 = 0xff;
 const SOC_MMU_INVALID: u32 = 1 << 8;
+const SOC_IRAM0_CACHE: Range<u32> = 0x42000000..0x42800000;
+const SOC_DRAM0_CACHE: Range<u32> = 0x3C000000..0x3C800000;
 
-pub fn mmu_ll_get_page_size(_mmu_id: u32) -> u32 {
-    crate::mmu_hal::MMU_PAGE_64KB
-}
+pub struct Esp32c3;
 
-pub fn mmu_ll_get_entry_id(_mmu_id: u32, vaddr: u32) -> u32 {
-    (vaddr & SOC_MMU_VADDR_MASK) >> 16
-}
+impl MmuLl for Esp32c3 {
+    fn get_page_size(_mmu: &impl MmuTable, _mmu_id: u32) -> u32 {
+        MMU_PAGE_64KB
+    }
+
+    fn check_valid_ext_vaddr_region(_mmu: &impl MmuTable, _mmu_id: u32, vaddr: u32) -> bool {
+        SOC_IRAM0_CACHE.contains(&vaddr) || SOC_DRAM0_CACHE.contains(&vaddr)
+    }
+
+    fn get_entry_id(_mmu: &impl MmuTable, _mmu_id: u32, vaddr: u32) -> u32 {
+        (vaddr & SOC_MMU_VADDR_MASK) >> 16
+    }
 
-pub fn mmu_ll_entry_id_to_paddr_base(_mmu_id: u32, entry_id: u32) -> u32 {
-    let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
-    unsafe { ((*ptr) & SOC_MMU_VALID_VAL_MASK) << 16 }
+    fn entry_id_to_paddr_base(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> u32 {
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_VALID_VAL_MASK) << 16
+    }
+
+    fn check_entry_valid(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> bool {
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_INVALID) == 0
+    }
+
+    fn get_entry_target(_mmu: &impl MmuTable, _mmu_id: u32, _entry_id: u32) -> MmuTarget {
+        MmuTarget::Flash
+    }
 }
 
-pub fn mmu_ll_check_entry_valid(_mmu_id: u32, entry_id: u32) -> bool {
-    let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
-    unsafe { ((*ptr) & SOC_MMU_INVALID) == 0 }
+#[cfg(feature = "esp32c3")]
+const DR_REG_MMU_TABLE: u32 = 0x600c5000;
+
+/// MMU registers of esp32c3
+#[cfg(feature = "esp32c3")]
+pub struct HwMmuTable;
+
+#[cfg(feature = "esp32c3")]
+impl MmuTable for HwMmuTable {
+    fn read_entry(&self, _mmu_id: u32, entry_id: u32) -> u32 {
+        let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
+        unsafe { *ptr }
+    }
+
+    fn page_size_code(&self, _mmu_id: u32) -> u32 {
+        0
+    }
 }
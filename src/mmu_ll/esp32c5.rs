@@ -0,0 +1,84 @@
+use super::{MmuLl, page_shift};
+use crate::mmu_hal::{
+    MMU_PAGE_8KB, MMU_PAGE_16KB, MMU_PAGE_32KB, MMU_PAGE_64KB, MmuTable, MmuTarget,
+};
+
+const SOC_MMU_ENTRY_NUM: u32 = 512;
+const SOC_MMU_VALID_VAL_MASK: u32 = 0x3ff;
+const SOC_MMU_ACCESS_SPIRAM: u32 = 1 << 10;
+const SOC_MMU_VALID: u32 = 1 << 11;
+const SOC_IRAM0_CACHE_ADDRESS_LOW: u32 = 0x42000000;
+
+/// Flash and PSRAM share one MMU (PSRAM entries have `SOC_MMU_ACCESS_SPIRAM` set)
+pub struct Esp32c5;
+
+impl Esp32c5 {
+    fn soc_mmu_vaddr_mask(mmu: &impl MmuTable, mmu_id: u32) -> u32 {
+        Self::get_page_size(mmu, mmu_id) * SOC_MMU_ENTRY_NUM - 1
+    }
+}
+
+impl MmuLl for Esp32c5 {
+    fn get_page_size(mmu: &impl MmuTable, mmu_id: u32) -> u32 {
+        match mmu.page_size_code(mmu_id) {
+            0 => MMU_PAGE_64KB,
+            1 => MMU_PAGE_32KB,
+            2 => MMU_PAGE_16KB,
+            _ => MMU_PAGE_8KB,
+        }
+    }
+
+    fn check_valid_ext_vaddr_region(mmu: &impl MmuTable, mmu_id: u32, vaddr: u32) -> bool {
+        let size = Self::get_page_size(mmu, mmu_id) * SOC_MMU_ENTRY_NUM;
+        (SOC_IRAM0_CACHE_ADDRESS_LOW..SOC_IRAM0_CACHE_ADDRESS_LOW + size).contains(&vaddr)
+    }
+
+    fn get_entry_id(mmu: &impl MmuTable, mmu_id: u32, vaddr: u32) -> u32 {
+        let shift_code = page_shift(Self::get_page_size(mmu, mmu_id));
+        (vaddr & Self::soc_mmu_vaddr_mask(mmu, mmu_id)) >> shift_code
+    }
+
+    fn entry_id_to_paddr_base(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> u32 {
+        let shift_code = page_shift(Self::get_page_size(mmu, mmu_id));
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_VALID_VAL_MASK) << shift_code
+    }
+
+    fn check_entry_valid(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> bool {
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_VALID) != 0
+    }
+
+    fn get_entry_target(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> MmuTarget {
+        match mmu.read_entry(mmu_id, entry_id) & SOC_MMU_ACCESS_SPIRAM {
+            0 => MmuTarget::Flash,
+            _ => MmuTarget::Psram,
+        }
+    }
+}
+
+#[cfg(feature = "esp32c5")]
+const DR_REG_SPIMEM0_BASE: u32 = 0x60002000;
+#[cfg(feature = "esp32c5")]
+const SPI_MEM_MMU_ITEM_CONTENT_REG: u32 = DR_REG_SPIMEM0_BASE + 0x37c;
+#[cfg(feature = "esp32c5")]
+const SPI_MEM_MMU_ITEM_INDEX_REG: u32 = DR_REG_SPIMEM0_BASE + 0x380;
+#[cfg(feature = "esp32c5")]
+const SPI_MEM_MMU_POWER_CTRL_REG: u32 = DR_REG_SPIMEM0_BASE + 0x384;
+
+/// MMU registers of esp32c5
+#[cfg(feature = "esp32c5")]
+pub struct HwMmuTable;
+
+#[cfg(feature = "esp32c5")]
+impl MmuTable for HwMmuTable {
+    fn read_entry(&self, _mmu_id: u32, entry_id: u32) -> u32 {
+        unsafe {
+            (SPI_MEM_MMU_ITEM_INDEX_REG as *mut u32).write_volatile(entry_id);
+            (SPI_MEM_MMU_ITEM_CONTENT_REG as *const u32).read_volatile()
+        }
+    }
+
+    fn page_size_code(&self, _mmu_id: u32) -> u32 {
+        let power_ctrl = unsafe { (SPI_MEM_MMU_POWER_CTRL_REG as *const u32).read_volatile() };
+        (power_ctrl >> 3) & 0x3
+    }
+}
@@ -1,79 +1,76 @@
-use crate::mmu_hal::{MMU_PAGE_8KB, MMU_PAGE_16KB, MMU_PAGE_32KB, MMU_PAGE_64KB};
+use super::{MmuLl, page_shift};
+use crate::mmu_hal::{
+    MMU_PAGE_8KB, MMU_PAGE_16KB, MMU_PAGE_32KB, MMU_PAGE_64KB, MmuTable, MmuTarget,
+};
 
 const SOC_MMU_ENTRY_NUM: u32 = 256;
 const SOC_MMU_VALID_VAL_MASK: u32 = 0x1ff;
 const SOC_MMU_VALID: u32 = 1 << 9;
+const SOC_IRAM0_CACHE_ADDRESS_LOW: u32 = 0x42000000;
 
-fn soc_mmu_vaddr_mask(mmu_id: u32) -> u32 {
-    mmu_ll_get_page_size(mmu_id) * SOC_MMU_ENTRY_NUM - 1
-}
-
-pub fn mmu_ll_get_page_size(_mmu_id: u32) -> u32 {
-    let spi_mem = unsafe { &*esp32c6::SPI0::ptr() };
-    let page_size_code = spi_mem.mmu_power_ctrl().read().spi_mmu_page_size().bits();
+pub struct Esp32c6;
 
-    match page_size_code {
-        0 => MMU_PAGE_64KB,
-        1 => MMU_PAGE_32KB,
-        2 => MMU_PAGE_16KB,
-        _ => MMU_PAGE_8KB,
+impl Esp32c6 {
+    fn soc_mmu_vaddr_mask(mmu: &impl MmuTable, mmu_id: u32) -> u32 {
+        Self::get_page_size(mmu, mmu_id) * SOC_MMU_ENTRY_NUM - 1
     }
 }
 
-pub fn mmu_ll_get_entry_id(mmu_id: u32, vaddr: u32) -> u32 {
-    let shift_code = match mmu_ll_get_page_size(mmu_id) {
-        MMU_PAGE_64KB => 16,
-        MMU_PAGE_32KB => 15,
-        MMU_PAGE_16KB => 14,
-        MMU_PAGE_8KB => 13,
-        _ => {
-            error!("mmu_ll_get_entry_id failed!");
-
-            0
+impl MmuLl for Esp32c6 {
+    fn get_page_size(mmu: &impl MmuTable, mmu_id: u32) -> u32 {
+        match mmu.page_size_code(mmu_id) {
+            0 => MMU_PAGE_64KB,
+            1 => MMU_PAGE_32KB,
+            2 => MMU_PAGE_16KB,
+            _ => MMU_PAGE_8KB,
         }
-    };
-
-    (vaddr & soc_mmu_vaddr_mask(mmu_id)) >> shift_code
-}
+    }
 
-pub fn mmu_ll_entry_id_to_paddr_base(mmu_id: u32, entry_id: u32) -> u32 {
-    let shift_code = match mmu_ll_get_page_size(mmu_id) {
-        MMU_PAGE_64KB => 16,
-        MMU_PAGE_32KB => 15,
-        MMU_PAGE_16KB => 14,
-        MMU_PAGE_8KB => 13,
-        _ => {
-            error!("mmu_ll_entry_id_to_paddr_base failed!");
+    fn check_valid_ext_vaddr_region(mmu: &impl MmuTable, mmu_id: u32, vaddr: u32) -> bool {
+        let size = Self::get_page_size(mmu, mmu_id) * SOC_MMU_ENTRY_NUM;
+        (SOC_IRAM0_CACHE_ADDRESS_LOW..SOC_IRAM0_CACHE_ADDRESS_LOW + size).contains(&vaddr)
+    }
 
-            0
-        }
-    };
+    fn get_entry_id(mmu: &impl MmuTable, mmu_id: u32, vaddr: u32) -> u32 {
+        let shift_code = page_shift(Self::get_page_size(mmu, mmu_id));
+        (vaddr & Self::soc_mmu_vaddr_mask(mmu, mmu_id)) >> shift_code
+    }
 
-    let spi_mem = unsafe { &*esp32c6::SPI0::ptr() };
-    spi_mem
-        .mmu_item_index()
-        .write(|w| unsafe { w.spi_mmu_item_index().bits(entry_id) });
+    fn entry_id_to_paddr_base(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> u32 {
+        let shift_code = page_shift(Self::get_page_size(mmu, mmu_id));
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_VALID_VAL_MASK) << shift_code
+    }
 
-    let mmu_item_content = spi_mem
-        .mmu_item_content()
-        .read()
-        .spi_mmu_item_content()
-        .bits();
+    fn check_entry_valid(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> bool {
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_VALID) != 0
+    }
 
-    (mmu_item_content & SOC_MMU_VALID_VAL_MASK) << shift_code
+    fn get_entry_target(_mmu: &impl MmuTable, _mmu_id: u32, _entry_id: u32) -> MmuTarget {
+        MmuTarget::Flash
+    }
 }
 
-pub fn mmu_ll_check_entry_valid(_mmu_id: u32, entry_id: u32) -> bool {
-    let spi_mem = unsafe { &*esp32c6::SPI0::ptr() };
-    spi_mem
-        .mmu_item_index()
-        .write(|w| unsafe { w.spi_mmu_item_index().bits(entry_id) });
+/// MMU registers of esp32c6
+#[cfg(feature = "esp32c6")]
+pub struct HwMmuTable;
 
-    let mmu_item_content = spi_mem
-        .mmu_item_content()
-        .read()
-        .spi_mmu_item_content()
-        .bits();
+#[cfg(feature = "esp32c6")]
+impl MmuTable for HwMmuTable {
+    fn read_entry(&self, _mmu_id: u32, entry_id: u32) -> u32 {
+        let spi_mem = unsafe { &*esp32c6::SPI0::ptr() };
+        spi_mem
+            .mmu_item_index()
+            .write(|w| unsafe { w.spi_mmu_item_index().bits(entry_id) });
 
-    (mmu_item_content & SOC_MMU_VALID) != 0
+        spi_mem
+            .mmu_item_content()
+            .read()
+            .spi_mmu_item_content()
+            .bits()
+    }
+
+    fn page_size_code(&self, _mmu_id: u32) -> u32 {
+        let spi_mem = unsafe { &*esp32c6::SPI0::ptr() };
+        spi_mem.mmu_power_ctrl().read().spi_mmu_page_size().bits() as u32
+    }
 }
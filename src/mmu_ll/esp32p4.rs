@@ -0,0 +1,99 @@
+use super::MmuLl;
+use crate::mmu_hal::{MMU_PAGE_64KB, MmuTable, MmuTarget};
+use core::ops::Range;
+
+/// Flash and PSRAM have separate MMUs (`SOC_MMU_PER_EXT_MEM_TARGET`)
+pub const MMU_LL_FLASH_MMU_ID: u32 = 0;
+pub const MMU_LL_PSRAM_MMU_ID: u32 = 1;
+
+const SOC_MMU_ENTRY_NUM: u32 = 1024;
+const SOC_MMU_FLASH_VALID: u32 = 1 << 12;
+const SOC_MMU_FLASH_VALID_VAL_MASK: u32 = 0xfff;
+const SOC_MMU_PSRAM_VALID: u32 = 1 << 11;
+const SOC_MMU_PSRAM_VALID_VAL_MASK: u32 = 0x7ff;
+const SOC_MMU_FLASH_VADDR: Range<u32> = 0x40000000..0x44000000;
+const SOC_MMU_PSRAM_VADDR: Range<u32> = 0x48000000..0x4C000000;
+
+pub struct Esp32p4;
+
+impl MmuLl for Esp32p4 {
+    fn get_mmu_id(vaddr: u32) -> u32 {
+        if SOC_MMU_PSRAM_VADDR.contains(&vaddr) {
+            MMU_LL_PSRAM_MMU_ID
+        } else {
+            MMU_LL_FLASH_MMU_ID
+        }
+    }
+
+    fn get_page_size(_mmu: &impl MmuTable, _mmu_id: u32) -> u32 {
+        MMU_PAGE_64KB
+    }
+
+    fn check_valid_ext_vaddr_region(_mmu: &impl MmuTable, mmu_id: u32, vaddr: u32) -> bool {
+        match mmu_id {
+            MMU_LL_FLASH_MMU_ID => SOC_MMU_FLASH_VADDR.contains(&vaddr),
+            _ => SOC_MMU_PSRAM_VADDR.contains(&vaddr),
+        }
+    }
+
+    fn get_entry_id(_mmu: &impl MmuTable, _mmu_id: u32, vaddr: u32) -> u32 {
+        (vaddr & (MMU_PAGE_64KB * SOC_MMU_ENTRY_NUM - 1)) >> 16
+    }
+
+    fn entry_id_to_paddr_base(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> u32 {
+        let mask = match mmu_id {
+            MMU_LL_FLASH_MMU_ID => SOC_MMU_FLASH_VALID_VAL_MASK,
+            _ => SOC_MMU_PSRAM_VALID_VAL_MASK,
+        };
+
+        (mmu.read_entry(mmu_id, entry_id) & mask) << 16
+    }
+
+    fn check_entry_valid(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> bool {
+        let valid = match mmu_id {
+            MMU_LL_FLASH_MMU_ID => SOC_MMU_FLASH_VALID,
+            _ => SOC_MMU_PSRAM_VALID,
+        };
+
+        (mmu.read_entry(mmu_id, entry_id) & valid) != 0
+    }
+
+    fn get_entry_target(_mmu: &impl MmuTable, mmu_id: u32, _entry_id: u32) -> MmuTarget {
+        match mmu_id {
+            MMU_LL_FLASH_MMU_ID => MmuTarget::Flash,
+            _ => MmuTarget::Psram,
+        }
+    }
+}
+
+#[cfg(feature = "esp32p4")]
+const DR_REG_FLASH_SPI0_BASE: u32 = 0x5008C000;
+#[cfg(feature = "esp32p4")]
+const DR_REG_PSRAM_MSPI0_BASE: u32 = 0x5008E000;
+#[cfg(feature = "esp32p4")]
+const SPI_MEM_MMU_ITEM_CONTENT_REG: u32 = 0x37c;
+#[cfg(feature = "esp32p4")]
+const SPI_MEM_MMU_ITEM_INDEX_REG: u32 = 0x380;
+
+/// MMU registers of esp32p4 (flash MMU in SPI0, PSRAM MMU in PSRAM MSPI0)
+#[cfg(feature = "esp32p4")]
+pub struct HwMmuTable;
+
+#[cfg(feature = "esp32p4")]
+impl MmuTable for HwMmuTable {
+    fn read_entry(&self, mmu_id: u32, entry_id: u32) -> u32 {
+        let base = match mmu_id {
+            MMU_LL_FLASH_MMU_ID => DR_REG_FLASH_SPI0_BASE,
+            _ => DR_REG_PSRAM_MSPI0_BASE,
+        };
+
+        unsafe {
+            ((base + SPI_MEM_MMU_ITEM_INDEX_REG) as *mut u32).write_volatile(entry_id);
+            ((base + SPI_MEM_MMU_ITEM_CONTENT_REG) as *const u32).read_volatile()
+        }
+    }
+
+    fn page_size_code(&self, _mmu_id: u32) -> u32 {
+        0
+    }
+}
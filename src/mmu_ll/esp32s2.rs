@@ -1,19 +1,17 @@
+use super::MmuLl;
+use crate::mmu_hal::{MMU_PAGE_64KB, MmuTable, MmuTarget};
+use core::ops::Range;
+
 const SOC_MMU_VADDR_MASK: u32 = 0x3FFFFF;
-const DR_REG_MMU_TABLE: u32 = 0x61801000;
 const SOC_MMU_VALID_VAL_MASK: u32 = 0x3fff;
 const SOC_MMU_INVALID: u32 = 1 << 14;
-const SOC_IRAM0_CACHE_ADDRESS_LOW: u32 = 0x40080000;
-const SOC_IRAM0_CACHE_ADDRESS_HIGH: u32 = 0x40400000;
-const SOC_IRAM1_ADDRESS_LOW: u32 = 0x40400000;
-const SOC_IRAM1_ADDRESS_HIGH: u32 = 0x40800000;
-const SOC_DROM0_ADDRESS_LOW: u32 = 0x3f000000;
-const SOC_DROM0_ADDRESS_HIGH: u32 = 0x3f400000;
-const SOC_DRAM0_CACHE_ADDRESS_LOW: u32 = 0x3fc00000;
-const SOC_DRAM0_CACHE_ADDRESS_HIGH: u32 = 0x3ff80000;
-const SOC_DRAM1_ADDRESS_LOW: u32 = 0x3f800000;
-const SOC_DRAM1_ADDRESS_HIGH: u32 = 0x3fc00000;
-const SOC_DPORT_CACHE_ADDRESS_LOW: u32 = 0x3f500000;
-const SOC_DPORT_CACHE_ADDRESS_HIGH: u32 = 0x3f800000;
+const SOC_MMU_ACCESS_FLASH: u32 = 1 << 15;
+const SOC_IRAM0_CACHE: Range<u32> = 0x40080000..0x40400000;
+const SOC_IRAM1: Range<u32> = 0x40400000..0x40800000;
+const SOC_DROM0: Range<u32> = 0x3f000000..0x3f400000;
+const SOC_DRAM0_CACHE: Range<u32> = 0x3fc00000..0x3ff80000;
+const SOC_DRAM1: Range<u32> = 0x3f800000..0x3fc00000;
+const SOC_DPORT_CACHE: Range<u32> = 0x3f500000..0x3f800000;
 const PRO_CACHE_IBUS0_MMU_START: u32 = 0;
 const PRO_CACHE_IBUS1_MMU_START: u32 = 0x100;
 const PRO_CACHE_IBUS2_MMU_START: u32 = 0x200;
@@ -21,38 +19,79 @@ const PRO_CACHE_DBUS0_MMU_START: u32 = 0x300;
 const PRO_CACHE_DBUS1_MMU_START: u32 = 0x400;
 const PRO_CACHE_DBUS2_MMU_START: u32 = 0x500;
 
-pub fn mmu_ll_get_page_size(_mmu_id: u32) -> u32 {
-    crate::mmu_hal::MMU_PAGE_64KB
-}
+pub struct Esp32s2;
 
-pub fn mmu_ll_get_entry_id(_mmu_id: u32, vaddr: u32) -> u32 {
-    let offset = if soc_address_in_bus!(SOC_DROM0, vaddr) {
-        PRO_CACHE_IBUS2_MMU_START / 4
-    } else if soc_address_in_bus!(SOC_IRAM0_CACHE, vaddr) {
-        PRO_CACHE_IBUS0_MMU_START / 4
-    } else if soc_address_in_bus!(SOC_IRAM1, vaddr) {
-        PRO_CACHE_IBUS1_MMU_START / 4
-    } else if soc_address_in_bus!(SOC_DPORT_CACHE, vaddr) {
-        PRO_CACHE_DBUS2_MMU_START / 4
-    } else if soc_address_in_bus!(SOC_DRAM1, vaddr) {
-        PRO_CACHE_DBUS1_MMU_START / 4
-    } else if soc_address_in_bus!(SOC_DRAM0_CACHE, vaddr) {
-        PRO_CACHE_DBUS0_MMU_START / 4
-    } else {
-        error!("mmu_ll_get_entry_id failed!");
+impl MmuLl for Esp32s2 {
+    fn get_page_size(_mmu: &impl MmuTable, _mmu_id: u32) -> u32 {
+        MMU_PAGE_64KB
+    }
 
-        0
-    };
+    fn check_valid_ext_vaddr_region(_mmu: &impl MmuTable, _mmu_id: u32, vaddr: u32) -> bool {
+        [
+            SOC_DROM0,
+            SOC_IRAM0_CACHE,
+            SOC_IRAM1,
+            SOC_DPORT_CACHE,
+            SOC_DRAM1,
+            SOC_DRAM0_CACHE,
+        ]
+        .iter()
+        .any(|bus| bus.contains(&vaddr))
+    }
 
-    offset + ((vaddr & SOC_MMU_VADDR_MASK) >> 16)
-}
+    fn get_entry_id(_mmu: &impl MmuTable, _mmu_id: u32, vaddr: u32) -> u32 {
+        let offset = if SOC_DROM0.contains(&vaddr) {
+            PRO_CACHE_IBUS2_MMU_START / 4
+        } else if SOC_IRAM0_CACHE.contains(&vaddr) {
+            PRO_CACHE_IBUS0_MMU_START / 4
+        } else if SOC_IRAM1.contains(&vaddr) {
+            PRO_CACHE_IBUS1_MMU_START / 4
+        } else if SOC_DPORT_CACHE.contains(&vaddr) {
+            PRO_CACHE_DBUS2_MMU_START / 4
+        } else if SOC_DRAM1.contains(&vaddr) {
+            PRO_CACHE_DBUS1_MMU_START / 4
+        } else if SOC_DRAM0_CACHE.contains(&vaddr) {
+            PRO_CACHE_DBUS0_MMU_START / 4
+        } else {
+            error!("mmu_ll_get_entry_id failed!");
+
+            0
+        };
+
+        offset + ((vaddr & SOC_MMU_VADDR_MASK) >> 16)
+    }
 
-pub fn mmu_ll_entry_id_to_paddr_base(_mmu_id: u32, entry_id: u32) -> u32 {
-    let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
-    unsafe { ((*ptr) & SOC_MMU_VALID_VAL_MASK) << 16 }
+    fn entry_id_to_paddr_base(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> u32 {
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_VALID_VAL_MASK) << 16
+    }
+
+    fn check_entry_valid(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> bool {
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_INVALID) == 0
+    }
+
+    fn get_entry_target(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> MmuTarget {
+        match mmu.read_entry(mmu_id, entry_id) & SOC_MMU_ACCESS_FLASH {
+            0 => MmuTarget::Psram,
+            _ => MmuTarget::Flash,
+        }
+    }
 }
 
-pub fn mmu_ll_check_entry_valid(_mmu_id: u32, entry_id: u32) -> bool {
-    let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
-    unsafe { ((*ptr) & SOC_MMU_INVALID) == 0 }
+#[cfg(feature = "esp32s2")]
+const DR_REG_MMU_TABLE: u32 = 0x61801000;
+
+/// MMU registers of esp32s2
+#[cfg(feature = "esp32s2")]
+pub struct HwMmuTable;
+
+#[cfg(feature = "esp32s2")]
+impl MmuTable for HwMmuTable {
+    fn read_entry(&self, _mmu_id: u32, entry_id: u32) -> u32 {
+        let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
+        unsafe { *ptr }
+    }
+
+    fn page_size_code(&self, _mmu_id: u32) -> u32 {
+        0
+    }
 }
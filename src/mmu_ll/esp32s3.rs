@@ -1,22 +1,60 @@
+use super::MmuLl;
+use crate::mmu_hal::{MMU_PAGE_64KB, MmuTable, MmuTarget};
+use core::ops::Range;
+
 const SOC_MMU_VADDR_MASK: u32 = 0x1FFFFFF;
-const DR_REG_MMU_TABLE: u32 = 0x600C5000;
 const SOC_MMU_VALID_VAL_MASK: u32 = 0x3fff;
 const SOC_MMU_INVALID: u32 = 1 << 14;
+const SOC_MMU_ACCESS_SPIRAM: u32 = 1 << 15;
+const SOC_IRAM0_CACHE: Range<u32> = 0x42000000..0x44000000;
+const SOC_DRAM0_CACHE: Range<u32> = 0x3C000000..0x3E000000;
 
-pub fn mmu_ll_get_page_size(_mmu_id: u32) -> u32 {
-    crate::mmu_hal::MMU_PAGE_64KB
-}
+pub struct Esp32s3;
 
-pub fn mmu_ll_get_entry_id(_mmu_id: u32, vaddr: u32) -> u32 {
-    (vaddr & SOC_MMU_VADDR_MASK) >> 16
-}
+impl MmuLl for Esp32s3 {
+    fn get_page_size(_mmu: &impl MmuTable, _mmu_id: u32) -> u32 {
+        MMU_PAGE_64KB
+    }
+
+    fn check_valid_ext_vaddr_region(_mmu: &impl MmuTable, _mmu_id: u32, vaddr: u32) -> bool {
+        SOC_IRAM0_CACHE.contains(&vaddr) || SOC_DRAM0_CACHE.contains(&vaddr)
+    }
+
+    fn get_entry_id(_mmu: &impl MmuTable, _mmu_id: u32, vaddr: u32) -> u32 {
+        (vaddr & SOC_MMU_VADDR_MASK) >> 16
+    }
 
-pub fn mmu_ll_entry_id_to_paddr_base(_mmu_id: u32, entry_id: u32) -> u32 {
-    let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
-    unsafe { ((*ptr) & SOC_MMU_VALID_VAL_MASK) << 16 }
+    fn entry_id_to_paddr_base(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> u32 {
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_VALID_VAL_MASK) << 16
+    }
+
+    fn check_entry_valid(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> bool {
+        (mmu.read_entry(mmu_id, entry_id) & SOC_MMU_INVALID) == 0
+    }
+
+    fn get_entry_target(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> MmuTarget {
+        match mmu.read_entry(mmu_id, entry_id) & SOC_MMU_ACCESS_SPIRAM {
+            0 => MmuTarget::Flash,
+            _ => MmuTarget::Psram,
+        }
+    }
 }
 
-pub fn mmu_ll_check_entry_valid(_mmu_id: u32, entry_id: u32) -> bool {
-    let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
-    unsafe { ((*ptr) & SOC_MMU_INVALID) == 0 }
+#[cfg(feature = "esp32s3")]
+const DR_REG_MMU_TABLE: u32 = 0x600C5000;
+
+/// MMU registers of esp32s3
+#[cfg(feature = "esp32s3")]
+pub struct HwMmuTable;
+
+#[cfg(feature = "esp32s3")]
+impl MmuTable for HwMmuTable {
+    fn read_entry(&self, _mmu_id: u32, entry_id: u32) -> u32 {
+        let ptr = (DR_REG_MMU_TABLE + entry_id * 4) as *const u32;
+        unsafe { *ptr }
+    }
+
+    fn page_size_code(&self, _mmu_id: u32) -> u32 {
+        0
+    }
 }
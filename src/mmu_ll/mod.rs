@@ -1,64 +1,108 @@
-#[cfg(any(feature = "esp32", feature = "esp32s2"))]
-macro_rules! soc_address_in_bus {
-    ($bus_name:ident, $vaddr:ident) => {{
-        (concat_idents!($bus_name, _ADDRESS_LOW)..concat_idents!($bus_name, _ADDRESS_HIGH))
-            .contains(&$vaddr)
-    }};
+//! Chip specific MMU decoding (like `mmu_ll.h` in ESP-IDF).
+//!
+//! Decoding of all chips is always compiled (so it can be tested on host with
+//! [`crate::mmu_hal::MockMmuTable`]), register access (`HwMmuTable`) only for selected chip.
+//! Without chip feature there is no `Chip`/`HwMmuTable` and no MMU lookup of running partition.
+
+use crate::mmu_hal::{
+    MMU_PAGE_8KB, MMU_PAGE_16KB, MMU_PAGE_32KB, MMU_PAGE_64KB, MmuTable, MmuTarget,
+};
+
+pub mod esp32;
+pub mod esp32c2;
+pub mod esp32c3;
+pub mod esp32c5;
+pub mod esp32c6;
+pub mod esp32c61;
+pub mod esp32h2;
+pub mod esp32p4;
+pub mod esp32s2;
+pub mod esp32s3;
+
+/// MMU decoding of single chip, registers are read through [`MmuTable`]
+pub trait MmuLl {
+    /// Returns id of MMU that maps vaddr (chips with single MMU always use 0)
+    fn get_mmu_id(_vaddr: u32) -> u32 {
+        0
+    }
+
+    /// Returns MMU page size in bytes
+    fn get_page_size(mmu: &impl MmuTable, mmu_id: u32) -> u32;
+
+    /// Checks if vaddr is in cached external memory (flash or PSRAM) region
+    fn check_valid_ext_vaddr_region(mmu: &impl MmuTable, mmu_id: u32, vaddr: u32) -> bool;
+
+    /// Returns id of MMU entry that maps vaddr
+    fn get_entry_id(mmu: &impl MmuTable, mmu_id: u32, vaddr: u32) -> u32;
+
+    /// Returns physical address of page mapped by entry
+    fn entry_id_to_paddr_base(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> u32;
+
+    fn check_entry_valid(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> bool;
+
+    fn get_entry_target(mmu: &impl MmuTable, mmu_id: u32, entry_id: u32) -> MmuTarget;
+
+    /// Translates vaddr to physical flash address, see `mmu_hal::vaddr_to_paddr`
+    fn vaddr_to_paddr(mmu: &impl MmuTable, mmu_id: u32, vaddr: u32) -> Option<u32> {
+        if !Self::check_valid_ext_vaddr_region(mmu, mmu_id, vaddr) {
+            return None;
+        }
+
+        crate::mmu_hal::vaddr_to_paddr_with(
+            vaddr,
+            Self::get_page_size(mmu, mmu_id),
+            |vaddr| Self::get_entry_id(mmu, mmu_id, vaddr),
+            |entry_id| {
+                let flash = Self::check_entry_valid(mmu, mmu_id, entry_id)
+                    && Self::get_entry_target(mmu, mmu_id, entry_id) == MmuTarget::Flash;
+
+                flash.then(|| Self::entry_id_to_paddr_base(mmu, mmu_id, entry_id))
+            },
+        )
+    }
+}
+
+/// Returns shift code of page size (vaddr/paddr >> shift is page number)
+fn page_shift(page_size: u32) -> u32 {
+    match page_size {
+        MMU_PAGE_64KB => 16,
+        MMU_PAGE_32KB => 15,
+        MMU_PAGE_16KB => 14,
+        MMU_PAGE_8KB => 13,
+        _ => {
+            error!("Wrong MMU page size! 0x{:X}", page_size);
+
+            0
+        }
+    }
 }
 
 #[cfg(feature = "esp32")]
-mod esp32;
-#[cfg(feature = "esp32")]
-pub use esp32::*;
+pub use esp32::{Esp32 as Chip, HwMmuTable};
 
 #[cfg(feature = "esp32s2")]
-mod esp32s2;
-#[cfg(feature = "esp32s2")]
-pub use esp32s2::*;
+pub use esp32s2::{Esp32s2 as Chip, HwMmuTable};
 
 #[cfg(feature = "esp32s3")]
-mod esp32s3;
-#[cfg(feature = "esp32s3")]
-pub use esp32s3::*;
+pub use esp32s3::{Esp32s3 as Chip, HwMmuTable};
 
 #[cfg(feature = "esp32c2")]
-mod esp32c2;
-#[cfg(feature = "esp32c2")]
-pub use esp32c2::*;
+pub use esp32c2::{Esp32c2 as Chip, HwMmuTable};
 
 #[cfg(feature = "esp32c3")]
-mod esp32c3;
-#[cfg(feature = "esp32c3")]
-pub use esp32c3::*;
+pub use esp32c3::{Esp32c3 as Chip, HwMmuTable};
 
 #[cfg(feature = "esp32c6")]
-mod esp32c6;
-#[cfg(feature = "esp32c6")]
-pub use esp32c6::*;
+pub use esp32c6::{Esp32c6 as Chip, HwMmuTable};
 
 #[cfg(feature = "esp32h2")]
-mod esp32h2;
-#[cfg(feature = "esp32h2")]
-pub use esp32h2::*;
-
-#[cfg(not(any(
-    feature = "esp32",
-    feature = "esp32s2",
-    feature = "esp32s3",
-    feature = "esp32c2",
-    feature = "esp32c3",
-    feature = "esp32c6",
-    feature = "esp32h2"
-)))]
-mod not_selected;
-
-#[cfg(not(any(
-    feature = "esp32",
-    feature = "esp32s2",
-    feature = "esp32s3",
-    feature = "esp32c2",
-    feature = "esp32c3",
-    feature = "esp32c6",
-    feature = "esp32h2"
-)))]
-pub use not_selected::*;
+pub use esp32h2::{Esp32h2 as Chip, HwMmuTable};
+
+#[cfg(feature = "esp32c5")]
+pub use esp32c5::{Esp32c5 as Chip, HwMmuTable};
+
+#[cfg(feature = "esp32c61")]
+pub use esp32c61::{Esp32c61 as Chip, HwMmuTable};
+
+#[cfg(feature = "esp32p4")]
+pub use esp32p4::{Esp32p4 as Chip, HwMmuTable};
@@ -0,0 +1,78 @@
+//! Reading app image back out of OTA partition (for backups or sharing with other devices).
+
+use crate::{Ota, Result};
+use embedded_storage::{ReadStorage, Storage};
+
+/// [`embedded_io::Read`] adapter over app image in OTA partition, created by
+/// [`Ota::image_reader`]
+///
+/// Only the image itself is read (up to its checksum and appended SHA-256),
+/// not the whole partition
+pub struct ImageReader<'a, S>
+where
+    S: ReadStorage + Storage,
+{
+    ota: &'a mut Ota<S>,
+    offset: u32,
+    size: u32,
+    pos: u32,
+}
+
+impl<S> Ota<S>
+where
+    S: ReadStorage + Storage,
+{
+    /// Returns reader of app image in OTA partition
+    ///
+    /// Image length is calculated from image header and segment headers
+    /// (see [`Ota::read_image_info`]), image digest isn't verified
+    pub fn image_reader(&mut self, partition: usize) -> Result<ImageReader<'_, S>> {
+        let info = self.read_image_info(partition)?;
+
+        Ok(ImageReader {
+            offset: self.pinfo.ota_partitions[partition].0,
+            size: info.size,
+            pos: 0,
+            ota: self,
+        })
+    }
+}
+
+impl<S> ImageReader<'_, S>
+where
+    S: ReadStorage + Storage,
+{
+    /// Length of whole image
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Number of bytes left to read
+    pub fn remaining(&self) -> u32 {
+        self.size - self.pos
+    }
+}
+
+impl<S> embedded_io::ErrorType for ImageReader<'_, S>
+where
+    S: ReadStorage + Storage,
+{
+    type Error = crate::OtaError;
+}
+
+impl<S> embedded_io::Read for ImageReader<'_, S>
+where
+    S: ReadStorage + Storage,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = (self.remaining() as usize).min(buf.len());
+        if n == 0 {
+            return Ok(0);
+        }
+
+        self.ota.read_flash(self.offset + self.pos, &mut buf[..n])?;
+        self.pos += n as u32;
+
+        Ok(n)
+    }
+}
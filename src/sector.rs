@@ -0,0 +1,182 @@
+//! Sector buffer of sequential writes.
+//!
+//! `Storage` drivers (like esp-storage) read, erase and rewrite every sector touched by
+//! a write, so forwarding small chunks would erase the same sector many times. Chunks are
+//! collected here and each sector is written at once.
+//!
+//! NOTE: there is no separate erase and page programming (`Storage` doesn't have them),
+//! every write is still read-modify-write of whole sector done by the driver. If partial
+//! sector is flushed before it's complete ([`Ota::ota_verify`], [`Ota::ota_write_at`],
+//! [`crate::writer::OtaWriter`] flush), that sector is erased again when it's completed.
+//!
+//! With [`Ota::set_compare_before_write`] sectors that already contain the same data
+//! (e.g. the same build flashed again) aren't erased and written at all.
+
+use crate::{Ota, OtaError, Result, WriteStats, crc32, journal, structs::OTA_BLOCK_SIZE};
+use embedded_storage::{ReadStorage, Storage};
+
+/// Flash sector size, sequential writes are buffered up to sector boundary
+pub const SECTOR_SIZE: usize = OTA_BLOCK_SIZE as usize;
+
+/// Bytes of image accepted by [`Ota::ota_write_chunk`] but not written to flash yet
+pub(crate) struct SectorBuffer {
+    data: [u8; SECTOR_SIZE],
+    len: usize,
+}
+
+impl SectorBuffer {
+    pub(crate) const fn new() -> Self {
+        Self {
+            data: [0; SECTOR_SIZE],
+            len: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Appends bytes of sector that starts being written at `flash_offset`
+    /// (buffer never crosses sector boundary). Returns number of bytes taken.
+    fn push(&mut self, flash_offset: u32, bytes: &[u8]) -> usize {
+        let free = SECTOR_SIZE - flash_offset as usize % SECTOR_SIZE - self.len;
+        let n = free.min(bytes.len());
+        self.data[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+        self.len += n;
+
+        n
+    }
+
+    fn is_full(&self, flash_offset: u32) -> bool {
+        (flash_offset as usize + self.len).is_multiple_of(SECTOR_SIZE)
+    }
+}
+
+impl<S> Ota<S>
+where
+    S: ReadStorage + Storage,
+{
+    /// Enables comparing every sector with target partition before it's written,
+    /// sectors with the same content are skipped (disabled by default)
+    ///
+    /// This costs a flash read of every sector, but saves erase and program of sectors
+    /// that didn't change (see [`crate::FlashProgress::stats`])
+    pub fn set_compare_before_write(&mut self, enabled: bool) {
+        self.compare_before_write = enabled;
+    }
+
+    /// Buffers sequential chunk, every completed sector is written to flash
+    pub(crate) fn buffer_chunk(&mut self, mut chunk: &[u8]) -> Result<()> {
+        while !chunk.is_empty() {
+            let flash_offset = self
+                .progress
+                .as_ref()
+                .ok_or(OtaError::OtaNotStarted)?
+                .flash_offset;
+
+            let n = self.sector.push(flash_offset, chunk);
+            chunk = &chunk[n..];
+            if self.sector.is_full(flash_offset) {
+                self.flush_sector()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes buffered part of sector to flash and advances progress
+    ///
+    /// Progress (and journal) only ever covers bytes that are already in flash,
+    /// so update can be resumed from it
+    pub(crate) fn flush_sector(&mut self) -> Result<()> {
+        let Some(progress) = self.progress.as_mut() else {
+            return Ok(());
+        };
+
+        let len = self.sector.len;
+        if len == 0 {
+            return Ok(());
+        }
+
+        let data = &self.sector.data[..len];
+        Self::program(
+            &mut self.flash,
+            self.compare_before_write,
+            progress.flash_offset,
+            data,
+            &mut progress.stats,
+        )?;
+
+        progress.last_crc = crc32::calc_crc32(data, progress.last_crc);
+
+        let checkpoint = (progress.flash_size - progress.remaining) / journal::JOURNAL_INTERVAL;
+        progress.flash_offset += len as u32;
+        progress.remaining -= len as u32;
+        progress.blocks.mark_prefix(
+            progress.flash_size - progress.remaining,
+            progress.flash_size,
+        );
+        self.sector.clear();
+
+        if (progress.flash_size - progress.remaining) / journal::JOURNAL_INTERVAL != checkpoint {
+            self.journal_checkpoint()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `data` to flash sector by sector (sector is skipped if it already contains
+    /// the same data and `compare` is set)
+    pub(crate) fn program(
+        flash: &mut S,
+        compare: bool,
+        mut offset: u32,
+        mut data: &[u8],
+        stats: &mut WriteStats,
+    ) -> Result<()> {
+        while !data.is_empty() {
+            let n = (SECTOR_SIZE - offset as usize % SECTOR_SIZE).min(data.len());
+            if compare && Self::flash_matches(flash, offset, &data[..n])? {
+                debug!("[OTA] Skipped {} bytes at 0x{:x} (same content)", n, offset);
+
+                stats.sectors_skipped += 1;
+                stats.bytes_skipped += n as u32;
+            } else {
+                flash
+                    .write(offset, &data[..n])
+                    .map_err(|_| OtaError::FlashWriteError { offset })?;
+
+                debug!("[OTA] Wrote {} bytes to ota partition at 0x{:x}", n, offset);
+
+                stats.sectors_written += 1;
+            }
+
+            offset += n as u32;
+            data = &data[n..];
+        }
+
+        Ok(())
+    }
+
+    fn flash_matches(flash: &mut S, offset: u32, data: &[u8]) -> Result<bool> {
+        let mut bytes = [0; crate::OTA_VERIFY_READ_SIZE];
+        for (i, expected) in data.chunks(crate::OTA_VERIFY_READ_SIZE).enumerate() {
+            let read_offset = offset + (i * crate::OTA_VERIFY_READ_SIZE) as u32;
+            flash
+                .read(read_offset, &mut bytes[..expected.len()])
+                .map_err(|_| OtaError::FlashReadError {
+                    offset: read_offset,
+                })?;
+
+            if bytes[..expected.len()] != *expected {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
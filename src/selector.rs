@@ -0,0 +1,152 @@
+//! Choosing OTA partition that update is written to.
+//!
+//! By default next partition after the running one is used (round-robin), which with
+//! more than two OTA partitions may overwrite the last known good image. Other strategy
+//! can be set with [`Ota::set_slot_strategy`], or any [`SlotSelector`] can be passed to
+//! [`Ota::ota_begin_with`].
+
+use crate::{Ota, OtaError, OtaImgState, Result, helpers};
+use embedded_storage::{ReadStorage, Storage};
+
+/// State of OTA partitions passed to [`SlotSelector`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotContext {
+    /// Number of OTA partitions
+    pub count: usize,
+    /// Currently running OTA partition (if it can be detected)
+    pub running: Option<usize>,
+    /// Seq and state of otadata entry pointing to each OTA partition (if any)
+    pub otadata: [Option<(u32, OtaImgState)>; 16],
+}
+
+impl SlotContext {
+    /// Returns partitions other than the running one, starting right after it
+    pub fn candidates(&self) -> impl Iterator<Item = usize> + '_ {
+        let start = self.running.map_or(0, |running| running + 1);
+        (0..self.count)
+            .map(move |i| (start + i) % self.count)
+            .filter(move |&partition| Some(partition) != self.running)
+    }
+
+    /// Returns not running partition with highest seq that bootloader could fall back to
+    pub fn last_valid(&self) -> Option<usize> {
+        self.candidates()
+            .filter_map(|partition| Some((partition, self.otadata[partition]?)))
+            .filter(|(_, (_, state))| {
+                matches!(
+                    state,
+                    OtaImgState::EspOtaImgValid | OtaImgState::EspOtaImgUndefined
+                )
+            })
+            .max_by_key(|(_, (seq, _))| *seq)
+            .map(|(partition, _)| partition)
+    }
+}
+
+/// Chooses OTA partition for new update, returns `None` if there is no suitable one
+pub trait SlotSelector {
+    fn select(&mut self, ctx: &SlotContext) -> Option<usize>;
+}
+
+impl<F: FnMut(&SlotContext) -> Option<usize>> SlotSelector for F {
+    fn select(&mut self, ctx: &SlotContext) -> Option<usize> {
+        self(ctx)
+    }
+}
+
+/// Built-in slot selection strategies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlotStrategy {
+    /// Partition after the running one (`ota_0` if it can't be detected)
+    #[default]
+    RoundRobin,
+    /// Partition with lowest otadata seq (never booted partitions first)
+    OldestSeq,
+    /// Like [`SlotStrategy::RoundRobin`], but never overwrites last valid image
+    /// (needs at least 3 OTA partitions)
+    KeepLastValid,
+    /// Always given partition
+    Explicit(usize),
+}
+
+impl SlotSelector for SlotStrategy {
+    fn select(&mut self, ctx: &SlotContext) -> Option<usize> {
+        match *self {
+            SlotStrategy::RoundRobin => ctx.candidates().next(),
+            SlotStrategy::OldestSeq => ctx
+                .candidates()
+                .min_by_key(|&partition| ctx.otadata[partition].map_or(0, |(seq, _)| seq)),
+            SlotStrategy::KeepLastValid => {
+                let last_valid = ctx.last_valid();
+                ctx.candidates()
+                    .find(|&partition| Some(partition) != last_valid)
+            }
+            SlotStrategy::Explicit(partition) => Some(partition),
+        }
+    }
+}
+
+impl<S> Ota<S>
+where
+    S: ReadStorage + Storage,
+{
+    /// Sets strategy used by [`Ota::ota_begin`] to choose target partition
+    pub fn set_slot_strategy(&mut self, strategy: SlotStrategy) {
+        self.slot_strategy = strategy;
+    }
+
+    /// Returns state of OTA partitions (as passed to [`SlotSelector`])
+    pub fn slot_context(&mut self) -> Result<SlotContext> {
+        let count = self.pinfo.ota_partitions_count;
+        let mut ctx = SlotContext {
+            count,
+            running: self.get_currently_booted_partition(),
+            otadata: [None; 16],
+        };
+
+        // if both entries point to the same partition, one with higher seq is used
+        let (slot1, slot2) = self.get_ota_boot_entries()?;
+        for entry in [slot1, slot2] {
+            if entry.seq == 0 {
+                continue;
+            }
+
+            let otadata = &mut ctx.otadata[helpers::seq_to_part(entry.seq, count)];
+            if otadata.is_none_or(|(seq, _)| seq < entry.seq) {
+                *otadata = Some((entry.seq, entry.ota_state));
+            }
+        }
+
+        Ok(ctx)
+    }
+
+    /// Returns OTA partition chosen by `selector`
+    ///
+    /// Fails with [`OtaError::NoSlotAvailable`] if selector doesn't choose any, or
+    /// with [`OtaError::PartitionRunning`] if it chooses the running one
+    pub fn select_slot(&mut self, selector: &mut impl SlotSelector) -> Result<usize> {
+        let ctx = self.slot_context()?;
+        let partition = selector.select(&ctx).ok_or(OtaError::NoSlotAvailable)?;
+        self.check_target_slot(partition)?;
+
+        Ok(partition)
+    }
+
+    pub(crate) fn check_target_slot(&self, partition: usize) -> Result<()> {
+        if partition >= self.pinfo.ota_partitions_count {
+            return Err(OtaError::InvalidPartition { partition });
+        }
+
+        if self.get_currently_booted_partition() == Some(partition) {
+            error!(
+                "[OTA] Cannot write update into running partition {}!",
+                partition
+            );
+
+            return Err(OtaError::PartitionRunning { partition });
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+//! Status report of all OTA partitions (like `otatool.py read_otadata` with image info).
+
+use crate::{EspAppDesc, Ota, OtaError, Result, SlotStatus};
+use embedded_storage::{ReadStorage, Storage};
+
+impl<S> Ota<S>
+where
+    S: ReadStorage + Storage,
+{
+    /// Returns status of every OTA partition (in partition table order)
+    ///
+    /// Otadata and image headers are re-read for each partition, so the iterator
+    /// should be consumed before flash is modified
+    pub fn slots_status(&mut self) -> impl Iterator<Item = Result<SlotStatus>> + '_ {
+        (0..self.pinfo.ota_partitions_count).map(move |partition| self.slot_status(partition))
+    }
+
+    /// Returns status of single OTA partition, see [`Ota::slots_status`]
+    pub fn slot_status(&mut self, partition: usize) -> Result<SlotStatus> {
+        if partition >= self.pinfo.ota_partitions_count {
+            return Err(OtaError::InvalidPartition { partition });
+        }
+
+        let ctx = self.slot_context()?;
+        let next_boot = self.get_boot_partition()?;
+
+        let image_valid = match self.read_image_info(partition) {
+            Ok(_) => true,
+            Err(OtaError::InvalidImage { .. }) => false,
+            Err(e) => return Err(e),
+        };
+
+        let (offset, size) = self.pinfo.ota_partitions[partition];
+        let app_version = match image_valid {
+            true => self.read_app_desc(offset)?.map(|desc| desc.version),
+            false => None,
+        };
+
+        Ok(SlotStatus {
+            partition,
+            offset,
+            size,
+            label: self.pinfo.ota_labels[partition],
+            running: ctx.running == Some(partition),
+            next_boot: next_boot == Some(partition),
+            otadata: ctx.otadata[partition],
+            image_valid,
+            app_version,
+        })
+    }
+
+    fn read_app_desc(&mut self, part_offset: u32) -> Result<Option<EspAppDesc>> {
+        let mut bytes = [0; EspAppDesc::SIZE];
+        self.read_flash(part_offset + EspAppDesc::IMAGE_OFFSET, &mut bytes)?;
+
+        Ok(EspAppDesc::from_bytes(&bytes))
+    }
+}
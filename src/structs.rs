@@ -1,15 +1,212 @@
 pub(crate) type Result<T> = core::result::Result<T, OtaError>;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum OtaError {
-    NotEnoughPartitions,
+    /// Partition table contains less than 2 OTA app partitions
+    NotEnoughPartitions {
+        found: usize,
+    },
     OtaNotStarted,
-    FlashRWError,
-    WrongCRC,
-    WrongOTAPArtitionOrder,
-    OtaVerifyError,
+    /// Reading flash at `offset` failed
+    FlashReadError {
+        offset: u32,
+    },
+    /// Writing flash at `offset` failed
+    FlashWriteError {
+        offset: u32,
+    },
+    /// Crc of received data doesn't match target crc
+    WrongCRC {
+        expected: u32,
+        calculated: u32,
+    },
+    /// OTA partitions aren't stored in order (ota_0, ota_1, ...)
+    WrongOTAPArtitionOrder {
+        expected: usize,
+        found: usize,
+    },
+    /// Crc of data read back from flash doesn't match target crc
+    OtaVerifyError {
+        expected: u32,
+        calculated: u32,
+    },
     CannotFindCurrentBootPartition,
+    /// Otadata only has slot 1 and slot 2
+    InvalidOtaDataSlot {
+        slot: u8,
+    },
+    /// Image doesn't fit into the target OTA partition
+    ImageTooLarge {
+        image_size: u32,
+        slot_size: u32,
+    },
+    /// Size passed to `ota_finish` doesn't match number of written bytes
+    ImageSizeMismatch {
+        expected: u32,
+        written: u32,
+    },
+    /// Update with unknown size wasn't finished using `ota_finish`
+    OtaNotFinished,
+    /// Journal region is unaligned or overlaps other partitions
+    InvalidJournalRegion {
+        offset: u32,
+    },
+    /// Operation needs image size, but update was started with `OTA_SIZE_UNKNOWN`
+    OtaSizeUnknown,
+    /// Random access write isn't aligned to `OTA_BLOCK_SIZE`
+    UnalignedWrite {
+        offset: u32,
+    },
+    /// Random access update is missing some blocks (`offset` is first missing byte)
+    MissingBlocks {
+        offset: u32,
+    },
+    /// `ota_write_chunk` can't be used after `ota_write_at`
+    RandomAccessInProgress,
+    /// There is no other valid image in otadata to roll back to
+    NoRollbackTarget,
+    /// There is no staged image in `partition`
+    NotStaged {
+        partition: usize,
+    },
+    /// There is no OTA partition with this index
+    InvalidPartition {
+        partition: usize,
+    },
+    /// OTA partition doesn't contain valid app image (wrong header or segments)
+    InvalidImage {
+        partition: usize,
+    },
+    /// Checksum or SHA-256 of app image in OTA partition doesn't match
+    ImageDigestMismatch {
+        partition: usize,
+    },
+    /// Operation isn't allowed on currently running OTA partition
+    PartitionRunning {
+        partition: usize,
+    },
+    /// Slot selector didn't choose any OTA partition for update
+    NoSlotAvailable,
+    /// Reading update source failed after `offset` bytes of image
+    SourceReadError {
+        offset: u32,
+    },
+    /// Progress passed to `ota_resume` has more bytes remaining than the whole image
+    InvalidResumeProgress {
+        flash_size: u32,
+        remaining: u32,
+    },
+}
+
+impl core::fmt::Display for OtaError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            OtaError::NotEnoughPartitions { found } => {
+                write!(f, "not enough OTA partitions (found {found}, need >= 2)")
+            }
+            OtaError::OtaNotStarted => write!(f, "OTA update wasn't started"),
+            OtaError::FlashReadError { offset } => write!(f, "flash read failed at 0x{offset:x}"),
+            OtaError::FlashWriteError { offset } => {
+                write!(f, "flash write failed at 0x{offset:x}")
+            }
+            OtaError::WrongCRC {
+                expected,
+                calculated,
+            } => write!(
+                f,
+                "wrong crc (expected 0x{expected:08x}, calculated 0x{calculated:08x})"
+            ),
+            OtaError::WrongOTAPArtitionOrder { expected, found } => write!(
+                f,
+                "wrong OTA partition order (expected ota_{expected}, found ota_{found})"
+            ),
+            OtaError::OtaVerifyError {
+                expected,
+                calculated,
+            } => write!(
+                f,
+                "verify failed (expected crc 0x{expected:08x}, flash has 0x{calculated:08x})"
+            ),
+            OtaError::CannotFindCurrentBootPartition => {
+                write!(f, "cannot find currently booted partition")
+            }
+            OtaError::InvalidOtaDataSlot { slot } => {
+                write!(f, "invalid otadata slot {slot} (use 1 or 2)")
+            }
+            OtaError::ImageTooLarge {
+                image_size,
+                slot_size,
+            } => write!(
+                f,
+                "image too large ({image_size} bytes, slot has {slot_size} bytes)"
+            ),
+            OtaError::ImageSizeMismatch { expected, written } => write!(
+                f,
+                "image size mismatch (expected {expected} bytes, written {written} bytes)"
+            ),
+            OtaError::OtaNotFinished => write!(f, "OTA update with unknown size wasn't finished"),
+            OtaError::InvalidJournalRegion { offset } => {
+                write!(f, "invalid journal region at 0x{offset:x}")
+            }
+            OtaError::OtaSizeUnknown => write!(f, "OTA update image size is unknown"),
+            OtaError::UnalignedWrite { offset } => {
+                write!(f, "write at 0x{offset:x} isn't aligned to block size")
+            }
+            OtaError::MissingBlocks { offset } => {
+                write!(f, "image is missing data at 0x{offset:x}")
+            }
+            OtaError::RandomAccessInProgress => {
+                write!(f, "sequential write during random access update")
+            }
+            OtaError::NoRollbackTarget => write!(f, "no valid image to roll back to"),
+            OtaError::NotStaged { partition } => {
+                write!(f, "no staged image in partition {partition}")
+            }
+            OtaError::InvalidPartition { partition } => {
+                write!(f, "OTA partition {partition} doesn't exist")
+            }
+            OtaError::InvalidImage { partition } => {
+                write!(f, "no valid app image in OTA partition {partition}")
+            }
+            OtaError::ImageDigestMismatch { partition } => {
+                write!(f, "app image digest mismatch in OTA partition {partition}")
+            }
+            OtaError::PartitionRunning { partition } => {
+                write!(f, "OTA partition {partition} is currently running")
+            }
+            OtaError::NoSlotAvailable => write!(f, "no OTA partition available for update"),
+            OtaError::SourceReadError { offset } => {
+                write!(f, "reading update source failed after {offset} bytes")
+            }
+            OtaError::InvalidResumeProgress {
+                flash_size,
+                remaining,
+            } => write!(
+                f,
+                "invalid resume progress ({remaining} of {flash_size} bytes remaining)"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for OtaError {}
+
+impl embedded_io::Error for OtaError {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            OtaError::InvalidImage { .. }
+            | OtaError::ImageDigestMismatch { .. }
+            | OtaError::WrongCRC { .. }
+            | OtaError::OtaVerifyError { .. } => embedded_io::ErrorKind::InvalidData,
+            OtaError::InvalidPartition { .. }
+            | OtaError::InvalidOtaDataSlot { .. }
+            | OtaError::UnalignedWrite { .. }
+            | OtaError::ImageTooLarge { .. } => embedded_io::ErrorKind::InvalidInput,
+            OtaError::PartitionRunning { .. } => embedded_io::ErrorKind::PermissionDenied,
+            _ => embedded_io::ErrorKind::Other,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -21,31 +218,209 @@ pub struct FlashProgress {
 
     pub target_partition: usize,
     pub target_crc: u32,
+
+    /// Update started with [`crate::OTA_SIZE_UNKNOWN`] and not finished yet
+    /// (`flash_size` is size of target partition until then)
+    pub size_unknown: bool,
+
+    /// Blocks ([`OTA_BLOCK_SIZE`]) of image that were already written
+    pub blocks: BlockBitmap,
+    /// Image was written out of order using `ota_write_at`, so `last_crc` isn't valid
+    /// and crc of whole image is calculated from flash in `ota_flush`
+    pub random_access: bool,
+
+    /// Flash sectors written or skipped so far
+    pub stats: WriteStats,
+}
+
+/// Flash sectors written by update (see `Ota::set_compare_before_write`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WriteStats {
+    /// Sectors (or their parts) that were erased and programmed
+    pub sectors_written: u32,
+    /// Sectors (or their parts) that already had the same content
+    pub sectors_skipped: u32,
+    /// Bytes of image in skipped sectors
+    pub bytes_skipped: u32,
+}
+
+/// Granularity of random access writes (flash sector size)
+pub const OTA_BLOCK_SIZE: u32 = 0x1000;
+
+/// Completion bitmap with one bit per [`OTA_BLOCK_SIZE`] block of image
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockBitmap {
+    words: [u32; BlockBitmap::MAX_BLOCKS / 32],
+}
+
+impl BlockBitmap {
+    /// Enough for 16MB image
+    pub const MAX_BLOCKS: usize = 4096;
+    pub const MAX_IMAGE_SIZE: u32 = Self::MAX_BLOCKS as u32 * OTA_BLOCK_SIZE;
+
+    pub const fn new() -> Self {
+        Self {
+            words: [0; Self::MAX_BLOCKS / 32],
+        }
+    }
+
+    /// Number of blocks needed for image of given size
+    pub const fn blocks_for(image_size: u32) -> u32 {
+        image_size.div_ceil(OTA_BLOCK_SIZE)
+    }
+
+    pub fn is_set(&self, block: u32) -> bool {
+        let block = block as usize;
+        block < Self::MAX_BLOCKS && self.words[block / 32] & (1 << (block % 32)) != 0
+    }
+
+    /// Marks block as written, returns false if it was already marked
+    pub fn set(&mut self, block: u32) -> bool {
+        if self.is_set(block) || block as usize >= Self::MAX_BLOCKS {
+            return false;
+        }
+
+        self.words[block as usize / 32] |= 1 << (block % 32);
+        true
+    }
+
+    /// Marks blocks covered by first `written` bytes of image
+    ///
+    /// Last (partial) block is only marked if whole image was written
+    pub fn mark_prefix(&mut self, written: u32, image_size: u32) {
+        let blocks = match written == image_size {
+            true => Self::blocks_for(written),
+            false => written / OTA_BLOCK_SIZE,
+        };
+
+        let blocks = (blocks as usize).min(Self::MAX_BLOCKS);
+        for word in &mut self.words[..blocks / 32] {
+            *word = u32::MAX;
+        }
+
+        for block in (blocks / 32 * 32)..blocks {
+            self.set(block as u32);
+        }
+    }
+
+    /// Number of marked blocks
+    pub fn count(&self) -> u32 {
+        self.words.iter().map(|w| w.count_ones()).sum()
+    }
+
+    /// Number of image bytes covered by marked blocks
+    pub fn covered_bytes(&self, image_size: u32) -> u32 {
+        let blocks = Self::blocks_for(image_size);
+        let mut covered = self.count() * OTA_BLOCK_SIZE;
+        if blocks > 0 && self.is_set(blocks - 1) {
+            covered -= blocks * OTA_BLOCK_SIZE - image_size;
+        }
+
+        covered
+    }
+
+    /// Returns iterator over byte ranges of image that weren't written yet
+    pub fn missing_ranges(&self, image_size: u32) -> MissingRanges<'_> {
+        MissingRanges {
+            bitmap: self,
+            block: 0,
+            image_size,
+        }
+    }
+}
+
+impl Default for BlockBitmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator over missing byte ranges of image, see [`BlockBitmap::missing_ranges`]
+pub struct MissingRanges<'a> {
+    bitmap: &'a BlockBitmap,
+    block: u32,
+    image_size: u32,
+}
+
+impl Iterator for MissingRanges<'_> {
+    type Item = core::ops::Range<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let blocks = BlockBitmap::blocks_for(self.image_size);
+        while self.block < blocks && self.bitmap.is_set(self.block) {
+            self.block += 1;
+        }
+
+        if self.block >= blocks {
+            return None;
+        }
+
+        let start = self.block;
+        while self.block < blocks && !self.bitmap.is_set(self.block) {
+            self.block += 1;
+        }
+
+        Some(start * OTA_BLOCK_SIZE..(self.block * OTA_BLOCK_SIZE).min(self.image_size))
+    }
 }
 
 #[derive(Debug)]
 pub struct PartitionInfo {
     pub ota_partitions: [(u32, u32); 16],
     pub ota_partitions_count: usize,
+    /// Labels of OTA partitions (as in partition table, NUL padded)
+    pub ota_labels: [[u8; 16]; 16],
 
     pub otadata_offset: u32,
     pub otadata_size: u32,
 }
 
-#[repr(u32)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum OtaImgState {
-    EspOtaImgNew = 0x0,
-    EspOtaImgPendingVerify = 0x1,
-    EspOtaImgValid = 0x2,
-    EspOtaImgInvalid = 0x3,
-    EspOtaImgAborted = 0x4,
-    EspOtaImgUndefined = 0xFFFFFFFF,
+    EspOtaImgNew,
+    EspOtaImgPendingVerify,
+    EspOtaImgValid,
+    EspOtaImgInvalid,
+    EspOtaImgAborted,
+    EspOtaImgUndefined,
+    /// Value that isn't defined by ESP-IDF (for example half-written word)
+    Unknown(u32),
 }
 
-#[repr(C)]
-#[derive(Debug)]
+impl From<u32> for OtaImgState {
+    fn from(value: u32) -> Self {
+        match value {
+            0x0 => OtaImgState::EspOtaImgNew,
+            0x1 => OtaImgState::EspOtaImgPendingVerify,
+            0x2 => OtaImgState::EspOtaImgValid,
+            0x3 => OtaImgState::EspOtaImgInvalid,
+            0x4 => OtaImgState::EspOtaImgAborted,
+            0xFFFFFFFF => OtaImgState::EspOtaImgUndefined,
+            value => OtaImgState::Unknown(value),
+        }
+    }
+}
+
+impl From<OtaImgState> for u32 {
+    fn from(state: OtaImgState) -> Self {
+        match state {
+            OtaImgState::EspOtaImgNew => 0x0,
+            OtaImgState::EspOtaImgPendingVerify => 0x1,
+            OtaImgState::EspOtaImgValid => 0x2,
+            OtaImgState::EspOtaImgInvalid => 0x3,
+            OtaImgState::EspOtaImgAborted => 0x4,
+            OtaImgState::EspOtaImgUndefined => 0xFFFFFFFF,
+            OtaImgState::Unknown(value) => value,
+        }
+    }
+}
+
+/// Otadata entry, stored as 32 little-endian bytes in flash
+///
+/// NOTE: [Entry struct (link to .h file)](https://github.com/espressif/esp-idf/blob/master/components/bootloader_support/include/esp_flash_partitions.h#L66)
+#[derive(Debug, Clone, PartialEq)]
 pub struct EspOtaSelectEntry {
     pub seq: u32,
     pub seq_label: [u8; 20],
@@ -54,6 +429,32 @@ pub struct EspOtaSelectEntry {
 }
 
 impl EspOtaSelectEntry {
+    pub const SIZE: usize = 32;
+
+    /// Decodes entry from raw flash bytes
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let mut seq_label = [0; 20];
+        seq_label.copy_from_slice(&bytes[4..24]);
+
+        Self {
+            seq: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            seq_label,
+            ota_state: u32::from_le_bytes(bytes[24..28].try_into().unwrap()).into(),
+            crc: u32::from_le_bytes(bytes[28..32].try_into().unwrap()),
+        }
+    }
+
+    /// Encodes entry into raw flash bytes
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.seq.to_le_bytes());
+        bytes[4..24].copy_from_slice(&self.seq_label);
+        bytes[24..28].copy_from_slice(&u32::from(self.ota_state).to_le_bytes());
+        bytes[28..32].copy_from_slice(&self.crc.to_le_bytes());
+
+        bytes
+    }
+
     /// Check if crc(of seq) is correct, if not - its setting seq to 0
     pub fn check_crc(&mut self) {
         if !crate::helpers::is_crc_seq_correct(self.seq, self.crc) {
@@ -61,3 +462,347 @@ impl EspOtaSelectEntry {
         }
     }
 }
+
+/// Partition table entry, stored as 32 little-endian bytes in flash
+///
+/// NOTE: [Entry struct (link to .h file)](https://github.com/espressif/esp-idf/blob/master/components/bootloader_support/include/esp_flash_partitions.h#L97)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionEntry {
+    pub p_type: u8,
+    pub p_subtype: u8,
+    pub offset: u32,
+    pub size: u32,
+    pub label: [u8; 16],
+    pub flags: u32,
+}
+
+impl PartitionEntry {
+    pub const SIZE: usize = 32;
+    pub const MAGIC: [u8; 2] = [0xAA, 0x50];
+
+    /// Decodes entry from raw flash bytes, returns `None` if magic doesn't match
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        if bytes[0..2] != Self::MAGIC {
+            return None;
+        }
+
+        let mut label = [0; 16];
+        label.copy_from_slice(&bytes[12..28]);
+
+        Some(Self {
+            p_type: bytes[2],
+            p_subtype: bytes[3],
+            offset: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            size: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            label,
+            flags: u32::from_le_bytes(bytes[28..32].try_into().unwrap()),
+        })
+    }
+
+    /// Encodes entry into raw flash bytes
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..2].copy_from_slice(&Self::MAGIC);
+        bytes[2] = self.p_type;
+        bytes[3] = self.p_subtype;
+        bytes[4..8].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.size.to_le_bytes());
+        bytes[12..28].copy_from_slice(&self.label);
+        bytes[28..32].copy_from_slice(&self.flags.to_le_bytes());
+
+        bytes
+    }
+
+    /// Returns label as str (trailing NULs are stripped)
+    pub fn label_str(&self) -> Option<&str> {
+        crate::helpers::c_str(&self.label)
+    }
+}
+
+/// Progress record stored in resume journal, 32 little-endian bytes in flash
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtaJournalRecord {
+    pub target_partition: u32,
+    /// Announced image size ([`crate::OTA_SIZE_UNKNOWN`] if unknown)
+    pub image_size: u32,
+    pub target_crc: u32,
+    /// Number of bytes written to target partition
+    pub written: u32,
+    /// Crc of first `written` bytes
+    pub last_crc: u32,
+}
+
+impl OtaJournalRecord {
+    pub const SIZE: usize = 32;
+    pub const MAGIC: u32 = 0x4A41544F; // "OTAJ"
+
+    /// Decodes record from raw flash bytes, returns `None` if magic or crc doesn't match
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        let word = |i: usize| u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+        if word(0) != Self::MAGIC || word(7) != crate::crc32::calc_crc32(&bytes[..28], 0) {
+            return None;
+        }
+
+        Some(Self {
+            target_partition: word(1),
+            image_size: word(2),
+            target_crc: word(3),
+            written: word(4),
+            last_crc: word(5),
+        })
+    }
+
+    /// Encodes record into raw flash bytes
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0xFF; Self::SIZE];
+        let words = [
+            Self::MAGIC,
+            self.target_partition,
+            self.image_size,
+            self.target_crc,
+            self.written,
+            self.last_crc,
+        ];
+
+        for (i, word) in words.iter().enumerate() {
+            bytes[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+
+        let crc = crate::crc32::calc_crc32(&bytes[..28], 0);
+        bytes[28..32].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+}
+
+/// Interrupted update found in resume journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OtaResumeInfo {
+    pub target_partition: usize,
+    /// Announced image size ([`crate::OTA_SIZE_UNKNOWN`] if unknown)
+    pub image_size: u32,
+    pub target_crc: u32,
+    /// Offset in image that should be requested next
+    pub next_offset: u32,
+}
+
+/// Verified image waiting for activation (see `Ota::ota_stage`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StagedImage {
+    pub partition: usize,
+    pub size: u32,
+    pub crc: u32,
+}
+
+/// App image header, stored as 24 little-endian bytes at start of app partition
+///
+/// NOTE: [Header struct (link to .h file)](https://github.com/espressif/esp-idf/blob/master/components/bootloader_support/include/esp_app_format.h)
+#[derive(Debug, Clone, PartialEq)]
+pub struct EspImageHeader {
+    pub magic: u8,
+    pub segment_count: u8,
+    pub spi_mode: u8,
+    pub spi_speed_size: u8,
+    pub entry_addr: u32,
+    pub wp_pin: u8,
+    pub spi_pin_drv: [u8; 3],
+    pub chip_id: u16,
+    pub min_chip_rev: u8,
+    pub min_chip_rev_full: u16,
+    pub max_chip_rev_full: u16,
+    pub reserved: [u8; 4],
+    /// If 1, SHA-256 of whole image is appended after checksum
+    pub hash_appended: u8,
+}
+
+impl EspImageHeader {
+    pub const SIZE: usize = 24;
+    pub const MAGIC: u8 = 0xE9;
+    pub const MAX_SEGMENTS: u8 = 16;
+
+    /// Decodes header from raw flash bytes
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+
+        Self {
+            magic: bytes[0],
+            segment_count: bytes[1],
+            spi_mode: bytes[2],
+            spi_speed_size: bytes[3],
+            entry_addr: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            wp_pin: bytes[8],
+            spi_pin_drv: [bytes[9], bytes[10], bytes[11]],
+            chip_id: u16_at(12),
+            min_chip_rev: bytes[14],
+            min_chip_rev_full: u16_at(15),
+            max_chip_rev_full: u16_at(17),
+            reserved: [bytes[19], bytes[20], bytes[21], bytes[22]],
+            hash_appended: bytes[23],
+        }
+    }
+
+    /// Encodes header into raw flash bytes
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0] = self.magic;
+        bytes[1] = self.segment_count;
+        bytes[2] = self.spi_mode;
+        bytes[3] = self.spi_speed_size;
+        bytes[4..8].copy_from_slice(&self.entry_addr.to_le_bytes());
+        bytes[8] = self.wp_pin;
+        bytes[9..12].copy_from_slice(&self.spi_pin_drv);
+        bytes[12..14].copy_from_slice(&self.chip_id.to_le_bytes());
+        bytes[14] = self.min_chip_rev;
+        bytes[15..17].copy_from_slice(&self.min_chip_rev_full.to_le_bytes());
+        bytes[17..19].copy_from_slice(&self.max_chip_rev_full.to_le_bytes());
+        bytes[19..23].copy_from_slice(&self.reserved);
+        bytes[23] = self.hash_appended;
+
+        bytes
+    }
+}
+
+/// App image segment header, stored as 8 little-endian bytes before segment data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EspImageSegmentHeader {
+    pub load_addr: u32,
+    pub data_len: u32,
+}
+
+impl EspImageSegmentHeader {
+    pub const SIZE: usize = 8;
+
+    /// Decodes segment header from raw flash bytes
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        Self {
+            load_addr: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            data_len: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+        }
+    }
+
+    /// Encodes segment header into raw flash bytes
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.load_addr.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.data_len.to_le_bytes());
+
+        bytes
+    }
+}
+
+/// App image found in OTA partition
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageInfo {
+    pub header: EspImageHeader,
+    /// Length of image (including checksum and appended SHA-256)
+    pub size: u32,
+    /// Appended SHA-256 of image (if `header.hash_appended` is set)
+    pub sha256: Option<[u8; 32]>,
+}
+
+/// App description placed at the start of first image segment
+///
+/// NOTE: [esp_app_desc_t (link to .h file)](https://github.com/espressif/esp-idf/blob/master/components/esp_app_format/include/esp_app_desc.h)
+#[derive(Debug, Clone, PartialEq)]
+pub struct EspAppDesc {
+    pub magic: u32,
+    pub secure_version: u32,
+    pub version: [u8; 32],
+    pub project_name: [u8; 32],
+    pub time: [u8; 16],
+    pub date: [u8; 16],
+    pub idf_ver: [u8; 32],
+    pub app_elf_sha256: [u8; 32],
+}
+
+impl EspAppDesc {
+    /// Only the fields above are decoded, rest of the 256 byte struct is skipped
+    pub const SIZE: usize = 208;
+    pub const MAGIC: u32 = 0xABCD5432;
+    /// Offset of app description from start of image (image header + first segment header)
+    pub const IMAGE_OFFSET: u32 = (EspImageHeader::SIZE + EspImageSegmentHeader::SIZE) as u32;
+
+    /// Decodes app description from raw flash bytes, returns `None` if magic doesn't match
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        let magic = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        if magic != Self::MAGIC {
+            return None;
+        }
+
+        Some(Self {
+            magic,
+            secure_version: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            version: bytes[16..48].try_into().unwrap(),
+            project_name: bytes[48..80].try_into().unwrap(),
+            time: bytes[80..96].try_into().unwrap(),
+            date: bytes[96..112].try_into().unwrap(),
+            idf_ver: bytes[112..144].try_into().unwrap(),
+            app_elf_sha256: bytes[144..176].try_into().unwrap(),
+        })
+    }
+
+    /// Encodes app description into raw flash bytes (reserved words are zeroed)
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.magic.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.secure_version.to_le_bytes());
+        bytes[16..48].copy_from_slice(&self.version);
+        bytes[48..80].copy_from_slice(&self.project_name);
+        bytes[80..96].copy_from_slice(&self.time);
+        bytes[96..112].copy_from_slice(&self.date);
+        bytes[112..144].copy_from_slice(&self.idf_ver);
+        bytes[144..176].copy_from_slice(&self.app_elf_sha256);
+
+        bytes
+    }
+
+    /// Returns app version as str (trailing NULs are stripped)
+    pub fn version_str(&self) -> Option<&str> {
+        crate::helpers::c_str(&self.version)
+    }
+}
+
+/// Way currently running OTA partition was detected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootPartitionSource {
+    /// Flash MMU mapping of running code
+    Mmu,
+    /// Otadata entry selected by bootloader (MMU lookup failed, e.g. code runs from PSRAM)
+    Otadata,
+}
+
+/// Status of single OTA partition, returned by [`crate::Ota::slots_status`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotStatus {
+    /// OTA partition index (0 for ota_0)
+    pub partition: usize,
+    pub offset: u32,
+    pub size: u32,
+    pub label: [u8; 16],
+    /// Currently running from this partition
+    pub running: bool,
+    /// Bootloader will boot this partition next
+    pub next_boot: bool,
+    /// Seq and state of otadata entry pointing to this partition (if any)
+    pub otadata: Option<(u32, OtaImgState)>,
+    /// Image header and segment headers are valid
+    pub image_valid: bool,
+    /// Version from app description (if image has one)
+    pub app_version: Option<[u8; 32]>,
+}
+
+impl SlotStatus {
+    /// Returns label as str (trailing NULs are stripped)
+    pub fn label_str(&self) -> Option<&str> {
+        crate::helpers::c_str(&self.label)
+    }
+
+    /// Returns app version as str (trailing NULs are stripped)
+    pub fn app_version_str(&self) -> Option<&str> {
+        self.app_version
+            .as_ref()
+            .and_then(|version| crate::helpers::c_str(version))
+    }
+}
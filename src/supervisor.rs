@@ -0,0 +1,197 @@
+//! Trial boot supervisor (like ESP-IDF app rollback flow).
+//!
+//! On first boots of a new image it runs health checks and marks the app valid,
+//! or rolls back after too many failed trial boots. Failed trial boots are counted
+//! in unused `seq_label` field of running image otadata entry (one cleared bit per boot),
+//! so counter survives resets and is cleared whenever new image is flashed.
+
+use crate::{Ota, OtaImgState, Result};
+use embedded_storage::{ReadStorage, Storage};
+
+/// Max number of trial boots that can be counted
+pub const MAX_TRIAL_BOOTS: u32 = 32;
+
+/// Health check that has to pass before new image is marked valid
+pub trait HealthCheck {
+    /// Returns true if healthy, it's called repeatedly until it passes or deadline is reached
+    fn check(&mut self) -> bool;
+}
+
+impl<F: FnMut() -> bool> HealthCheck for F {
+    fn check(&mut self) -> bool {
+        self()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SupervisorOutcome {
+    /// Running image isn't in trial (already valid, or rollbacks aren't used)
+    NotInTrial(OtaImgState),
+    /// Health checks passed, image was marked valid
+    MarkedValid,
+    /// Health checks failed, device should be reset to try again
+    RetryTrial { attempt: u32, max_attempts: u32 },
+    /// Health checks failed too many times, device should be reset to boot `partition`
+    RolledBack { partition: usize },
+}
+
+pub struct RollbackSupervisor {
+    max_trial_boots: u32,
+    deadline_ms: u64,
+}
+
+impl RollbackSupervisor {
+    /// `max_trial_boots` - number of trial boots before rolling back (1..=[`MAX_TRIAL_BOOTS`])
+    /// `deadline_ms` - time health checks have to pass in
+    pub const fn new(max_trial_boots: u32, deadline_ms: u64) -> Self {
+        let max_trial_boots = match max_trial_boots {
+            0 => 1,
+            n if n > MAX_TRIAL_BOOTS => MAX_TRIAL_BOOTS,
+            n => n,
+        };
+
+        Self {
+            max_trial_boots,
+            deadline_ms,
+        }
+    }
+
+    /// Starts trial boot (if running image is new) and runs health checks until all
+    /// of them pass or deadline is reached
+    ///
+    /// `now_ms` - monotonic clock in milliseconds
+    pub fn run<S>(
+        &self,
+        ota: &mut Ota<S>,
+        checks: &mut [&mut dyn HealthCheck],
+        mut now_ms: impl FnMut() -> u64,
+    ) -> Result<SupervisorOutcome>
+    where
+        S: ReadStorage + Storage,
+    {
+        if let Some(outcome) = self.start_trial(ota)? {
+            return Ok(outcome);
+        }
+
+        let start = now_ms();
+        let mut passed: u64 = 0; // checks that already passed aren't repeated
+        loop {
+            let mut all_passed = true;
+            for (i, check) in checks.iter_mut().enumerate() {
+                let bit = 1u64.checked_shl(i as u32).unwrap_or(0);
+                if passed & bit != 0 {
+                    continue;
+                }
+
+                if check.check() {
+                    passed |= bit;
+                } else {
+                    all_passed = false;
+                }
+            }
+
+            if all_passed {
+                return self.finish_trial(ota, true);
+            }
+
+            if now_ms().saturating_sub(start) >= self.deadline_ms {
+                warn!("[OTA] Health checks didn't pass in time!");
+
+                return self.finish_trial(ota, false);
+            }
+        }
+    }
+
+    /// Starts trial boot, for running health checks yourself (for example async)
+    ///
+    /// Returns `None` if running image is in trial and health checks should be run,
+    /// their result is then reported using [`RollbackSupervisor::finish_trial`].
+    /// Returns outcome if there is nothing to check (or there were too many attempts).
+    pub fn start_trial<S>(&self, ota: &mut Ota<S>) -> Result<Option<SupervisorOutcome>>
+    where
+        S: ReadStorage + Storage,
+    {
+        let state = ota.get_ota_image_state()?;
+        if !matches!(
+            state,
+            OtaImgState::EspOtaImgNew | OtaImgState::EspOtaImgPendingVerify
+        ) {
+            return Ok(Some(SupervisorOutcome::NotInTrial(state)));
+        }
+
+        let attempts = ota.get_trial_boots()?;
+        if attempts >= self.max_trial_boots {
+            // device was reset during all trial boots
+            warn!("[OTA] Too many trial boots! Rolling back...");
+
+            let partition = ota.ota_rollback()?;
+            return Ok(Some(SupervisorOutcome::RolledBack { partition }));
+        }
+
+        ota.increment_trial_boots()?;
+        if state == OtaImgState::EspOtaImgNew {
+            ota.set_current_ota_state(OtaImgState::EspOtaImgPendingVerify)?;
+        }
+
+        info!("[OTA] Trial boot {}/{}", attempts + 1, self.max_trial_boots);
+        Ok(None)
+    }
+
+    /// Finishes trial boot started with [`RollbackSupervisor::start_trial`]
+    pub fn finish_trial<S>(&self, ota: &mut Ota<S>, healthy: bool) -> Result<SupervisorOutcome>
+    where
+        S: ReadStorage + Storage,
+    {
+        if healthy {
+            ota.ota_mark_app_valid()?;
+            return Ok(SupervisorOutcome::MarkedValid);
+        }
+
+        let attempt = ota.get_trial_boots()?;
+        if attempt >= self.max_trial_boots {
+            warn!(
+                "[OTA] Health checks failed {} times! Rolling back...",
+                attempt
+            );
+
+            let partition = ota.ota_rollback()?;
+            return Ok(SupervisorOutcome::RolledBack { partition });
+        }
+
+        Ok(SupervisorOutcome::RetryTrial {
+            attempt,
+            max_attempts: self.max_trial_boots,
+        })
+    }
+}
+
+impl<S> Ota<S>
+where
+    S: ReadStorage + Storage,
+{
+    /// Returns number of trial boots of running image
+    pub fn get_trial_boots(&mut self) -> Result<u32> {
+        let (_, entry) = self.get_current_slot()?;
+        let counter = u32::from_le_bytes(entry.seq_label[..4].try_into().unwrap());
+
+        Ok((!counter).count_ones())
+    }
+
+    fn increment_trial_boots(&mut self) -> Result<()> {
+        let (slot, entry) = self.get_current_slot()?;
+        let counter = u32::from_le_bytes(entry.seq_label[..4].try_into().unwrap());
+
+        // clear lowest set bit, so it can be written without erase
+        let counter = counter & counter.wrapping_sub(1);
+        self.write_flash(
+            self.get_otadata_entry_offset(slot)? + 4,
+            &counter.to_le_bytes(),
+        )
+    }
+
+    fn set_current_ota_state(&mut self, state: OtaImgState) -> Result<()> {
+        let (slot, _) = self.get_current_slot()?;
+        self.set_ota_state(slot, state)
+    }
+}
@@ -0,0 +1,204 @@
+//! Writing updates through `embedded_io` traits, so any transport (socket, uart, file)
+//! can be copied into OTA partition without hand written loop around [`Ota::ota_write_chunk`].
+
+use crate::{OTA_SIZE_UNKNOWN, Ota, OtaError, Result};
+use embedded_storage::{ReadStorage, Storage};
+
+/// Size of buffer used by [`Ota::update_from_reader`] (one flash sector)
+pub const UPDATE_CHUNK_SIZE: usize = 4096;
+
+/// [`embedded_io::Write`] (and [`embedded_io_async::Write`]) adapter over started update,
+/// created by [`Ota::writer`]
+///
+/// Writes are passed to [`Ota::ota_write_chunk`], so update has to be started
+/// (and later flushed) on [`Ota`] itself
+pub struct OtaWriter<'a, S>
+where
+    S: ReadStorage + Storage,
+{
+    ota: &'a mut Ota<S>,
+}
+
+impl<S> Ota<S>
+where
+    S: ReadStorage + Storage,
+{
+    /// Returns writer of update started using [`Ota::ota_begin`]
+    pub fn writer(&mut self) -> OtaWriter<'_, S> {
+        OtaWriter { ota: self }
+    }
+
+    /// Starts update (see [`Ota::ota_begin`]), copies whole image from `reader`
+    /// and flushes it (`verify` and `rollback` are passed to [`Ota::ota_flush`])
+    ///
+    /// `size` can be [`OTA_SIZE_UNKNOWN`], then image ends when reader returns EOF.
+    /// With known size reader isn't read past the image.
+    ///
+    /// If reader fails or ends before whole image was read, update is aborted
+    /// (see [`Ota::ota_abort`])
+    pub fn update_from_reader<R>(
+        &mut self,
+        reader: &mut R,
+        size: u32,
+        target_crc: u32,
+        verify: bool,
+        rollback: bool,
+    ) -> Result<()>
+    where
+        R: embedded_io::Read,
+    {
+        self.ota_begin(size, target_crc)?;
+
+        let mut buf = [0; UPDATE_CHUNK_SIZE];
+        let mut written = 0;
+        loop {
+            let len = read_len(size, written);
+            let n = reader
+                .read(&mut buf[..len])
+                .map_err(|_| self.abort_update(source_read_error(written)))?;
+            if n == 0 {
+                break;
+            }
+
+            written += n as u32;
+            if self.ota_write_chunk(&buf[..n])? {
+                break;
+            }
+        }
+
+        self.finish_from_reader(size, written, target_crc, verify, rollback)
+    }
+
+    /// Async version of [`Ota::update_from_reader`] (flash is still written blocking)
+    pub async fn update_from_reader_async<R>(
+        &mut self,
+        reader: &mut R,
+        size: u32,
+        target_crc: u32,
+        verify: bool,
+        rollback: bool,
+    ) -> Result<()>
+    where
+        R: embedded_io_async::Read,
+    {
+        self.ota_begin(size, target_crc)?;
+
+        let mut buf = [0; UPDATE_CHUNK_SIZE];
+        let mut written = 0;
+        loop {
+            let len = read_len(size, written);
+            let n = reader
+                .read(&mut buf[..len])
+                .await
+                .map_err(|_| self.abort_update(source_read_error(written)))?;
+            if n == 0 {
+                break;
+            }
+
+            written += n as u32;
+            if self.ota_write_chunk(&buf[..n])? {
+                break;
+            }
+        }
+
+        self.finish_from_reader(size, written, target_crc, verify, rollback)
+    }
+
+    fn finish_from_reader(
+        &mut self,
+        size: u32,
+        written: u32,
+        target_crc: u32,
+        verify: bool,
+        rollback: bool,
+    ) -> Result<()> {
+        if size == OTA_SIZE_UNKNOWN {
+            self.ota_finish(written, target_crc)?;
+        } else if written != size {
+            error!("[OTA] Reader ended early! ({} != {} bytes)", written, size);
+
+            return Err(self.abort_update(OtaError::ImageSizeMismatch {
+                expected: size,
+                written,
+            }));
+        }
+
+        self.ota_flush(verify, rollback)
+    }
+
+    /// Aborts update after reading update source failed, returns `err`
+    fn abort_update(&mut self, err: OtaError) -> OtaError {
+        // original error is more useful than abort failure
+        if self.ota_abort(false).is_err() {
+            warn!("[OTA] Aborting update failed!");
+        }
+
+        err
+    }
+}
+
+fn read_len(size: u32, written: u32) -> usize {
+    match size {
+        OTA_SIZE_UNKNOWN => UPDATE_CHUNK_SIZE,
+        _ => (size - written).min(UPDATE_CHUNK_SIZE as u32) as usize,
+    }
+}
+
+fn source_read_error(written: u32) -> OtaError {
+    error!(
+        "[OTA] Reading update source failed after {} bytes!",
+        written
+    );
+
+    OtaError::SourceReadError { offset: written }
+}
+
+impl<S> embedded_io::ErrorType for OtaWriter<'_, S>
+where
+    S: ReadStorage + Storage,
+{
+    type Error = OtaError;
+}
+
+impl<S> embedded_io::Write for OtaWriter<'_, S>
+where
+    S: ReadStorage + Storage,
+{
+    /// Writes as much of `buf` as fits into image, fails with
+    /// [`OtaError::ImageSizeMismatch`] if whole image was already written
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let progress = self.ota.progress.as_ref().ok_or(OtaError::OtaNotStarted)?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let (flash_size, size_unknown) = (progress.flash_size, progress.size_unknown);
+        match self.ota.write_chunk(buf)? {
+            0 if !size_unknown => Err(OtaError::ImageSizeMismatch {
+                expected: flash_size,
+                written: flash_size + buf.len() as u32,
+            }),
+            n => Ok(n),
+        }
+    }
+
+    /// Writes buffered part of flash sector (use [`Ota::ota_flush`] to finish update)
+    ///
+    /// NOTE: partial sector is erased again once rest of it is written
+    fn flush(&mut self) -> Result<()> {
+        self.ota.flush_sector()
+    }
+}
+
+impl<S> embedded_io_async::Write for OtaWriter<'_, S>
+where
+    S: ReadStorage + Storage,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        embedded_io::Write::write(self, buf)
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        embedded_io::Write::flush(self)
+    }
+}
@@ -0,0 +1,241 @@
+#![allow(dead_code)]
+
+use std::{cell::RefCell, rc::Rc};
+
+use embedded_storage::{ReadStorage, Storage};
+use esp_hal_ota::PartitionEntry;
+
+pub const FLASH_SIZE: usize = 0x100000;
+pub const PART_TABLE_OFFSET: u32 = 0x8000;
+pub const OTADATA_OFFSET: u32 = 0xd000;
+pub const OTADATA_SIZE: u32 = 0x2000;
+pub const OTA_OFFSET: u32 = 0x10000;
+pub const OTA_SIZE: u32 = 0x40000;
+pub const JOURNAL_OFFSET: u32 = 0xf000;
+pub const NVS_OFFSET: u32 = 0x9000;
+pub const NVS_SIZE: u32 = 0x4000;
+pub const SECTOR_SIZE: u32 = 0x1000;
+
+/// Offset of `ota_{idx}` partition in [`MockFlash`]
+pub const fn ota_offset(idx: usize) -> u32 {
+    OTA_OFFSET + idx as u32 * OTA_SIZE
+}
+
+/// Flash operations counted by [`MockFlash`]
+#[derive(Debug, Clone, Default)]
+pub struct FlashStats {
+    /// `Storage::write` calls
+    pub writes: usize,
+    /// Erase count of every sector
+    pub erases: Vec<u32>,
+}
+
+impl FlashStats {
+    /// Total number of sector erases in `offset..offset + len`
+    pub fn erases_in(&self, offset: u32, len: u32) -> u32 {
+        let first = (offset / SECTOR_SIZE) as usize;
+        let last = (offset + len).div_ceil(SECTOR_SIZE) as usize;
+        self.erases.iter().skip(first).take(last - first).sum()
+    }
+}
+
+/// In-memory flash with a partition table (nvs, otadata, journal + `ota_partitions` OTA slots)
+///
+/// Clones share the same backing memory, so flash content survives dropping [`esp_hal_ota::Ota`]
+/// (like it would survive a reset)
+///
+/// Writes are counted like esp-storage does them: every sector touched by
+/// `Storage::write` is read, erased and programmed again
+#[derive(Clone)]
+pub struct MockFlash {
+    pub data: Rc<RefCell<Vec<u8>>>,
+    pub fail_write_at: Option<u32>,
+    pub stats: Rc<RefCell<FlashStats>>,
+}
+
+impl MockFlash {
+    pub fn new() -> Self {
+        Self::with_ota_partitions(2)
+    }
+
+    pub fn with_ota_partitions(ota_partitions: usize) -> Self {
+        let flash = Self {
+            data: Rc::new(RefCell::new(vec![0xFF; FLASH_SIZE])),
+            fail_write_at: None,
+            stats: Rc::default(),
+        };
+
+        let mut entries = vec![
+            partition_entry(1, 2, NVS_OFFSET, NVS_SIZE, "nvs"),
+            partition_entry(1, 0, OTADATA_OFFSET, OTADATA_SIZE, "otadata"),
+            partition_entry(1, 6, JOURNAL_OFFSET, 0x1000, "ota_journal"),
+        ];
+        for i in 0..ota_partitions {
+            entries.push(partition_entry(
+                0,
+                0x10 + i as u8,
+                ota_offset(i),
+                OTA_SIZE,
+                &format!("ota_{i}"),
+            ));
+        }
+
+        for (i, entry) in entries.iter().enumerate() {
+            let offset = PART_TABLE_OFFSET as usize + i * PartitionEntry::SIZE;
+            flash.write_raw(offset as u32, &entry.to_bytes());
+        }
+
+        flash
+    }
+
+    pub fn slice(&self, offset: u32, len: usize) -> Vec<u8> {
+        self.data.borrow()[offset as usize..offset as usize + len].to_vec()
+    }
+
+    /// Returns flash operations counted since last call
+    pub fn take_stats(&self) -> FlashStats {
+        self.stats.take()
+    }
+
+    pub fn write_raw(&self, offset: u32, bytes: &[u8]) {
+        self.data.borrow_mut()[offset as usize..offset as usize + bytes.len()]
+            .copy_from_slice(bytes);
+    }
+}
+
+fn partition_entry(
+    p_type: u8,
+    p_subtype: u8,
+    offset: u32,
+    size: u32,
+    name: &str,
+) -> PartitionEntry {
+    let mut label = [0; 16];
+    label[..name.len()].copy_from_slice(name.as_bytes());
+
+    PartitionEntry {
+        p_type,
+        p_subtype,
+        offset,
+        size,
+        label,
+        flags: 0,
+    }
+}
+
+#[derive(Debug)]
+pub struct MockFlashError;
+
+impl ReadStorage for MockFlash {
+    type Error = MockFlashError;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        if offset + bytes.len() > FLASH_SIZE {
+            return Err(MockFlashError);
+        }
+
+        bytes.copy_from_slice(&self.data.borrow()[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        FLASH_SIZE
+    }
+}
+
+impl Storage for MockFlash {
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        if self
+            .fail_write_at
+            .is_some_and(|fail| (offset..offset + bytes.len() as u32).contains(&fail))
+        {
+            return Err(MockFlashError);
+        }
+
+        if offset as usize + bytes.len() > FLASH_SIZE {
+            return Err(MockFlashError);
+        }
+
+        let mut stats = self.stats.borrow_mut();
+        stats.erases.resize(FLASH_SIZE / SECTOR_SIZE as usize, 0);
+        stats.writes += 1;
+        let end = offset + bytes.len() as u32;
+        for sector in offset / SECTOR_SIZE..end.div_ceil(SECTOR_SIZE) {
+            stats.erases[sector as usize] += 1;
+        }
+        drop(stats);
+
+        self.write_raw(offset, bytes);
+        Ok(())
+    }
+}
+
+/// Deterministic pseudo-random firmware image
+pub fn firmware(len: usize) -> Vec<u8> {
+    let mut state = 0x1234_5678u32;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+pub fn crc(data: &[u8]) -> u32 {
+    esp_hal_ota::crc32::calc_crc32(data, 0)
+}
+
+/// Builds app image (like `espflash save-image`) with given segments
+pub fn app_image(segments: &[(u32, &[u8])], hash_appended: bool) -> Vec<u8> {
+    use esp_hal_ota::{EspImageHeader, EspImageSegmentHeader};
+    use sha2::{Digest, Sha256};
+
+    let header = EspImageHeader {
+        magic: EspImageHeader::MAGIC,
+        segment_count: segments.len() as u8,
+        spi_mode: 2,
+        spi_speed_size: 0x1f,
+        entry_addr: 0x4200_0000,
+        wp_pin: 0xEE,
+        spi_pin_drv: [0; 3],
+        chip_id: 5,
+        min_chip_rev: 0,
+        min_chip_rev_full: 0,
+        max_chip_rev_full: 0xFFFF,
+        reserved: [0; 4],
+        hash_appended: hash_appended as u8,
+    };
+
+    let mut image = header.to_bytes().to_vec();
+    let mut checksum = 0xEF;
+    for (load_addr, data) in segments {
+        let segment = EspImageSegmentHeader {
+            load_addr: *load_addr,
+            data_len: data.len() as u32,
+        };
+
+        image.extend_from_slice(&segment.to_bytes());
+        image.extend_from_slice(data);
+        checksum = data.iter().fold(checksum, |acc, b| acc ^ b);
+    }
+
+    while !(image.len() + 1).is_multiple_of(16) {
+        image.push(0);
+    }
+    image.push(checksum);
+
+    if hash_appended {
+        let hash = Sha256::digest(&image);
+        image.extend_from_slice(&hash);
+    }
+
+    image
+}
+
+/// Writes `image` to `ota_{idx}` partition
+pub fn install_image(flash: &MockFlash, idx: usize, image: &[u8]) {
+    flash.write_raw(ota_offset(idx), image);
+}
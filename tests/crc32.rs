@@ -0,0 +1,59 @@
+mod common;
+
+use common::firmware;
+use esp_hal_ota::crc32::calc_crc32;
+
+/// Byte at a time crc (previous implementation)
+fn reference_crc32(buf: &[u8], crc: u32) -> u32 {
+    let mut crc = !crc;
+    for &byte in buf {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB88320,
+                _ => crc >> 1,
+            };
+        }
+    }
+
+    !crc
+}
+
+#[test]
+fn matches_crc32fast() {
+    let data = firmware(10_000);
+    for len in (0..64).chain([255, 256, 4095, 4096, 4097, 10_000]) {
+        assert_eq!(
+            calc_crc32(&data[..len], 0),
+            crc32fast::hash(&data[..len]),
+            "len {len}"
+        );
+    }
+
+    assert_eq!(calc_crc32(b"123456789", 0), 0xCBF43926);
+}
+
+#[test]
+fn chained_chunks() {
+    let data = firmware(10_000);
+    for chunk_size in [1, 3, 7, 8, 9, 1000, 4096] {
+        let crc = data
+            .chunks(chunk_size)
+            .fold(0, |crc, chunk| calc_crc32(chunk, crc));
+
+        assert_eq!(crc, crc32fast::hash(&data), "chunk size {chunk_size}");
+    }
+}
+
+#[test]
+fn matches_previous_implementation() {
+    let data = firmware(1000);
+    for (start, len) in [(0, 0), (1, 13), (3, 100), (5, 995)] {
+        for crc in [0, 1, 0xFFFFFFFF, 0xDEADBEEF] {
+            assert_eq!(
+                calc_crc32(&data[start..start + len], crc),
+                reference_crc32(&data[start..start + len], crc)
+            );
+        }
+    }
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::*;
+use esp_hal_ota::{Ota, OtaError};
+
+fn small_image() -> Vec<u8> {
+    app_image(&[(0x3C00_0020, &firmware(64))], false)
+}
+
+/// Flash with app image in `partition` that is booted (selected in otadata)
+fn booted_flash(partition: usize) -> MockFlash {
+    let flash = MockFlash::new();
+    install_image(&flash, partition, &small_image());
+    Ota::new(flash.clone())
+        .unwrap()
+        .switch_to(partition, false)
+        .unwrap();
+
+    flash
+}
+
+#[test]
+fn erase_slot_clears_partition() {
+    let flash = booted_flash(0);
+    install_image(&flash, 1, &firmware(0x5123));
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.erase_slot(1).unwrap();
+
+    assert_eq!(
+        flash.slice(ota_offset(1), OTA_SIZE as usize),
+        vec![0xFF; OTA_SIZE as usize]
+    );
+    let image = small_image();
+    assert_eq!(flash.slice(ota_offset(0), image.len()), image);
+    assert_eq!(
+        ota.erase_slot(2),
+        Err(OtaError::InvalidPartition { partition: 2 })
+    );
+}
+
+#[test]
+fn erase_slot_refuses_running_partition() {
+    let mut ota = Ota::new(booted_flash(0)).unwrap();
+    assert_eq!(
+        ota.erase_slot(0),
+        Err(OtaError::PartitionRunning { partition: 0 })
+    );
+
+    // running partition unknown, it could be any of them
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+    assert_eq!(
+        ota.erase_slot(1),
+        Err(OtaError::CannotFindCurrentBootPartition)
+    );
+}
+
+#[test]
+fn erase_slot_skips_erased_sectors() {
+    let mut flash = booted_flash(1);
+    install_image(&flash, 0, &firmware(0x1000));
+    // any write outside of first sector would fail
+    flash.fail_write_at = Some(ota_offset(0) + 0x1000);
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.erase_slot(0).unwrap();
+    assert_eq!(flash.slice(ota_offset(0), 0x1000), vec![0xFF; 0x1000]);
+}
+
+#[test]
+fn erase_slot_aborts_update_into_it() {
+    let image = firmware(0x2000);
+    let mut ota = Ota::new(booted_flash(1)).unwrap();
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image[..0x1000]).unwrap();
+
+    // ota_1 is running, so update goes to ota_0
+    ota.erase_slot(0).unwrap();
+    assert_eq!(
+        ota.ota_write_chunk(&image[0x1000..]),
+        Err(OtaError::OtaNotStarted)
+    );
+}
+
+#[test]
+fn reset_otadata_erases_both_entries() {
+    let flash = MockFlash::new();
+    install_image(&flash, 1, &small_image());
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.switch_to(1, false).unwrap();
+    ota.switch_to(1, false).unwrap();
+    assert_ne!(
+        flash.slice(OTADATA_OFFSET, OTADATA_SIZE as usize),
+        vec![0xFF; OTADATA_SIZE as usize]
+    );
+
+    ota.reset_otadata().unwrap();
+    assert_eq!(
+        flash.slice(OTADATA_OFFSET, OTADATA_SIZE as usize),
+        vec![0xFF; OTADATA_SIZE as usize]
+    );
+    let (slot1, slot2) = ota.get_ota_boot_entries().unwrap();
+    assert_eq!((slot1.seq, slot2.seq), (0, 0));
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use common::*;
+use esp_hal_ota::{Ota, OtaError, OtaImgState};
+
+fn test_image(hash_appended: bool) -> Vec<u8> {
+    let drom = firmware(1000);
+    let irom = firmware(3333);
+    app_image(&[(0x3C00_0020, &drom), (0x4200_0020, &irom)], hash_appended)
+}
+
+#[test]
+fn image_info_and_verify() {
+    for hash_appended in [false, true] {
+        let image = test_image(hash_appended);
+        let flash = MockFlash::new();
+        install_image(&flash, 1, &image);
+
+        let mut ota = Ota::new(flash).unwrap();
+        let info = ota.verify_image(1).unwrap();
+        assert_eq!(info.size, image.len() as u32);
+        assert_eq!(info.header.segment_count, 2);
+        assert_eq!(info.sha256.is_some(), hash_appended);
+        assert_eq!(ota.read_image_info(1), Ok(info));
+    }
+}
+
+#[test]
+fn corrupted_image_is_rejected() {
+    for (hash_appended, corrupt_at) in [(false, 100), (true, 100), (true, 0x10)] {
+        let mut image = test_image(hash_appended);
+        // flipping two bits in same byte keeps xor checksum of header intact, but not sha
+        image[corrupt_at] ^= 0x03;
+
+        let flash = MockFlash::new();
+        install_image(&flash, 0, &image);
+
+        let mut ota = Ota::new(flash).unwrap();
+        assert!(ota.read_image_info(0).is_ok());
+        assert_eq!(
+            ota.verify_image(0),
+            Err(OtaError::ImageDigestMismatch { partition: 0 })
+        );
+    }
+}
+
+#[test]
+fn missing_image_is_rejected() {
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+    assert_eq!(
+        ota.verify_image(0),
+        Err(OtaError::InvalidImage { partition: 0 })
+    );
+    assert_eq!(
+        ota.verify_image(2),
+        Err(OtaError::InvalidPartition { partition: 2 })
+    );
+}
+
+#[test]
+fn segment_larger_than_partition_is_rejected() {
+    let mut image = test_image(false);
+    // first segment data_len
+    image[28..32].copy_from_slice(&(OTA_SIZE).to_le_bytes());
+
+    let flash = MockFlash::new();
+    install_image(&flash, 0, &image);
+
+    let mut ota = Ota::new(flash).unwrap();
+    assert_eq!(
+        ota.read_image_info(0),
+        Err(OtaError::InvalidImage { partition: 0 })
+    );
+}
+
+#[test]
+fn switch_to_installed_image() {
+    let flash = MockFlash::new();
+    install_image(&flash, 0, &test_image(true));
+    install_image(&flash, 1, &test_image(false));
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.switch_to(1, false).unwrap();
+    let (slot1, slot2) = ota.get_ota_boot_entries().unwrap();
+    assert_eq!((slot1.seq, slot2.seq), (2, 0));
+    assert_eq!(slot1.ota_state, OtaImgState::EspOtaImgUndefined);
+
+    ota.switch_to(0, true).unwrap();
+    let (slot1, slot2) = ota.get_ota_boot_entries().unwrap();
+    assert_eq!((slot1.seq, slot2.seq), (2, 3));
+    assert_eq!(slot2.ota_state, OtaImgState::EspOtaImgNew);
+}
+
+#[test]
+fn switch_to_invalid_image_keeps_otadata() {
+    let flash = MockFlash::new();
+    let mut image = test_image(true);
+    image[200] ^= 0xFF;
+    install_image(&flash, 1, &image);
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    assert_eq!(
+        ota.switch_to(1, false),
+        Err(OtaError::ImageDigestMismatch { partition: 1 })
+    );
+    assert_eq!(
+        flash.slice(OTADATA_OFFSET, OTADATA_SIZE as usize),
+        vec![0xFF; OTADATA_SIZE as usize]
+    );
+}
+
+#[test]
+fn slot_sha256_covers_image_without_appended_hash() {
+    use sha2::{Digest, Sha256};
+
+    let flash = MockFlash::new();
+    let plain = test_image(false);
+    let hashed = test_image(true);
+    install_image(&flash, 0, &plain);
+    install_image(&flash, 1, &hashed);
+
+    let mut ota = Ota::new(flash).unwrap();
+    assert_eq!(
+        ota.slot_sha256(0).unwrap(),
+        <[u8; 32]>::from(Sha256::digest(&plain))
+    );
+    assert_eq!(ota.slot_sha256(1).unwrap(), hashed[hashed.len() - 32..]);
+    assert_eq!(
+        ota.running_image_sha256(),
+        Err(OtaError::CannotFindCurrentBootPartition)
+    );
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use common::*;
+use esp_hal_ota::{
+    OTA_SIZE_UNKNOWN, Ota, OtaError, OtaImgState, OtaResumeInfo,
+    journal::{JOURNAL_INTERVAL, JOURNAL_SIZE},
+};
+
+#[test]
+fn journal_region_must_be_in_data_partition() {
+    for offset in [
+        OTADATA_OFFSET,
+        ota_offset(1),
+        PART_TABLE_OFFSET,
+        NVS_OFFSET,
+        JOURNAL_OFFSET + 1,
+        // not in any partition
+        ota_offset(2),
+    ] {
+        assert_eq!(
+            Ota::new_with_journal(MockFlash::new(), offset).err(),
+            Some(OtaError::InvalidJournalRegion { offset })
+        );
+    }
+
+    assert!(Ota::new_with_journal(MockFlash::new(), JOURNAL_OFFSET).is_ok());
+}
+
+#[test]
+fn journal_partition_must_be_undefined_subtype() {
+    // fat, spiffs, littlefs
+    for subtype in [0x81, 0x82, 0x83] {
+        let flash = MockFlash::new();
+        // subtype byte of ota_journal entry (third one in the table)
+        flash.write_raw(PART_TABLE_OFFSET + 2 * 32 + 3, &[subtype]);
+
+        assert_eq!(
+            Ota::new_with_journal(flash, JOURNAL_OFFSET).err(),
+            Some(OtaError::InvalidJournalRegion {
+                offset: JOURNAL_OFFSET
+            })
+        );
+    }
+}
+
+#[test]
+fn no_interrupted_update() {
+    let mut ota = Ota::new_with_journal(MockFlash::new(), JOURNAL_OFFSET).unwrap();
+    assert_eq!(ota.ota_resume_from_journal(), Ok(None));
+
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+    assert_eq!(ota.ota_resume_from_journal(), Ok(None));
+}
+
+#[test]
+fn resume_interrupted_update() {
+    let image = firmware(3 * JOURNAL_INTERVAL as usize + 1234);
+    let flash = MockFlash::new();
+
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    for chunk in image[..2 * JOURNAL_INTERVAL as usize + 5000].chunks(1024) {
+        ota.ota_write_chunk(chunk).unwrap();
+    }
+    drop(ota); // reset
+
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+    let info = ota.ota_resume_from_journal().unwrap().unwrap();
+    assert_eq!(
+        info,
+        OtaResumeInfo {
+            target_partition: 0,
+            image_size: image.len() as u32,
+            target_crc: crc(&image),
+            next_offset: 2 * JOURNAL_INTERVAL,
+        }
+    );
+
+    for chunk in image[info.next_offset as usize..].chunks(1024) {
+        ota.ota_write_chunk(chunk).unwrap();
+    }
+    ota.ota_flush(true, true).unwrap();
+
+    // journal is cleared after successful update
+    assert_eq!(
+        flash.slice(JOURNAL_OFFSET, JOURNAL_SIZE as usize),
+        vec![0xFF; JOURNAL_SIZE as usize]
+    );
+    let mut ota = Ota::new_with_journal(flash, JOURNAL_OFFSET).unwrap();
+    assert_eq!(ota.ota_resume_from_journal(), Ok(None));
+}
+
+#[test]
+fn journal_of_running_partition_is_discarded() {
+    let image = app_image(
+        &[(0x3C00_0020, &firmware(JOURNAL_INTERVAL as usize + 1000))],
+        false,
+    );
+    let flash = MockFlash::new();
+
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image[..JOURNAL_INTERVAL as usize + 10])
+        .unwrap();
+    // reset after boot partition was switched, before journal was cleared
+    ota.set_target_ota_boot_partition(0, OtaImgState::EspOtaImgNew)
+        .unwrap();
+    drop(ota);
+
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+    assert_eq!(ota.get_currently_booted_partition(), Some(0));
+    assert_eq!(ota.ota_resume_from_journal(), Ok(None));
+    assert_eq!(
+        flash.slice(JOURNAL_OFFSET, JOURNAL_SIZE as usize),
+        vec![0xFF; JOURNAL_SIZE as usize]
+    );
+    assert_eq!(ota.ota_write_chunk(&image), Err(OtaError::OtaNotStarted));
+}
+
+#[test]
+fn resume_restarts_if_written_data_is_corrupted() {
+    let image = firmware(2 * JOURNAL_INTERVAL as usize);
+    let flash = MockFlash::new();
+
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image[..JOURNAL_INTERVAL as usize + 10])
+        .unwrap();
+    drop(ota);
+
+    flash.write_raw(ota_offset(0) + 100, &[0x00]);
+
+    let mut ota = Ota::new_with_journal(flash, JOURNAL_OFFSET).unwrap();
+    let info = ota.ota_resume_from_journal().unwrap().unwrap();
+    assert_eq!(info.next_offset, 0);
+
+    assert_eq!(ota.ota_write_chunk(&image), Ok(true));
+    ota.ota_flush(true, true).unwrap();
+}
+
+#[test]
+fn resume_unknown_size_update() {
+    let image = firmware(JOURNAL_INTERVAL as usize + 3000);
+    let flash = MockFlash::new();
+
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+    ota.ota_begin(OTA_SIZE_UNKNOWN, 0).unwrap();
+    ota.ota_write_chunk(&image[..JOURNAL_INTERVAL as usize + 100])
+        .unwrap();
+    drop(ota);
+
+    let mut ota = Ota::new_with_journal(flash, JOURNAL_OFFSET).unwrap();
+    let info = ota.ota_resume_from_journal().unwrap().unwrap();
+    assert_eq!(info.image_size, OTA_SIZE_UNKNOWN);
+    // last 100 bytes were still in sector buffer
+    assert_eq!(info.next_offset, JOURNAL_INTERVAL);
+
+    ota.ota_write_chunk(&image[info.next_offset as usize..])
+        .unwrap();
+    ota.ota_finish(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_flush(true, true).unwrap();
+}
+
+#[test]
+fn journal_wraps_when_full() {
+    // more checkpoints than records fitting into journal sector
+    let image = firmware(OTA_SIZE as usize);
+    let flash = MockFlash::new();
+
+    for _ in 0..40 {
+        let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+        ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+        ota.ota_write_chunk(&image[..3 * JOURNAL_INTERVAL as usize])
+            .unwrap();
+    }
+
+    // begin + 3 checkpoints per update, full journal is erased at once
+    assert_eq!(
+        flash.take_stats().erases_in(JOURNAL_OFFSET, JOURNAL_SIZE),
+        40 * 4 + 1
+    );
+
+    let mut ota = Ota::new_with_journal(flash, JOURNAL_OFFSET).unwrap();
+    let info = ota.ota_resume_from_journal().unwrap().unwrap();
+    assert_eq!(info.next_offset, 3 * JOURNAL_INTERVAL);
+}
@@ -0,0 +1,214 @@
+use esp_hal_ota::{
+    mmu_hal::{self, MMU_PAGE_8KB, MMU_PAGE_16KB, MMU_PAGE_32KB, MmuTarget, MockMmuTable},
+    mmu_ll::{
+        MmuLl, esp32::Esp32, esp32c2::Esp32c2, esp32c3::Esp32c3, esp32c5::Esp32c5,
+        esp32c6::Esp32c6, esp32c61::Esp32c61, esp32h2::Esp32h2, esp32p4::Esp32p4, esp32s2::Esp32s2,
+        esp32s3::Esp32s3,
+    },
+};
+
+type Mmu = MockMmuTable<2048>;
+
+#[test]
+fn esp32c3_decoding() {
+    // entry: 8 bit page number, bit 8 = invalid
+    let mut mmu = Mmu::new(1 << 8);
+    mmu.set_entry(0, 0, 0x01);
+    mmu.set_entry(0, 5, 0x15);
+
+    assert_eq!(Esp32c3::vaddr_to_paddr(&mmu, 0, 0x4200_0020), Some(0x10020));
+    assert_eq!(
+        Esp32c3::vaddr_to_paddr(&mmu, 0, 0x3C05_1234),
+        Some(0x151234)
+    );
+    assert_eq!(Esp32c3::vaddr_to_paddr(&mmu, 0, 0x4201_0000), None);
+    // internal SRAM
+    assert_eq!(Esp32c3::vaddr_to_paddr(&mmu, 0, 0x3FC8_0000), None);
+}
+
+#[test]
+fn esp32s3_decoding() {
+    // entry: 14 bit page number, bit 14 = invalid, bit 15 = PSRAM
+    let mut mmu = Mmu::new(1 << 14);
+    mmu.set_entry(0, 1, 0x02);
+    mmu.set_entry(0, 2, (1 << 15) | 0x02);
+
+    assert_eq!(Esp32s3::get_entry_id(&mmu, 0, 0x4201_0000), 1);
+    assert_eq!(Esp32s3::vaddr_to_paddr(&mmu, 0, 0x4201_0010), Some(0x20010));
+    assert_eq!(Esp32s3::get_entry_target(&mmu, 0, 2), MmuTarget::Psram);
+    assert_eq!(Esp32s3::vaddr_to_paddr(&mmu, 0, 0x3C02_0000), None);
+    assert!(Esp32s3::check_valid_ext_vaddr_region(&mmu, 0, 0x43FF_FFFF));
+    assert!(!Esp32s3::check_valid_ext_vaddr_region(&mmu, 0, 0x4400_0000));
+}
+
+#[test]
+fn esp32_decoding() {
+    // entry: page number, bit 8 = invalid, entries from 1152 map PSRAM (32KB pages)
+    let mut mmu = Mmu::new(1 << 8);
+    mmu.set_entry(0, 0, 0x01);
+    mmu.set_entry(0, 77, 0x02);
+    mmu.set_entry(0, 1152, 0x00);
+
+    // app text starts at 0x400D0018 (IRAM0 cache bus, entries from 64)
+    assert_eq!(Esp32::get_entry_id(&mmu, 0, 0x400D_0018), 77);
+    assert_eq!(Esp32::vaddr_to_paddr(&mmu, 0, 0x400D_0018), Some(0x20018));
+    assert_eq!(Esp32::vaddr_to_paddr(&mmu, 0, 0x3F40_0020), Some(0x10020));
+    assert_eq!(Esp32::get_entry_id(&mmu, 0, 0x4080_0000), 192);
+    assert_eq!(Esp32::get_entry_id(&mmu, 0, 0x4040_0000), 128);
+
+    assert_eq!(Esp32::get_entry_id(&mmu, 0, 0x3F80_8000), 1153);
+    assert_eq!(Esp32::get_entry_target(&mmu, 0, 1152), MmuTarget::Psram);
+    assert_eq!(Esp32::vaddr_to_paddr(&mmu, 0, 0x3F80_0000), None);
+}
+
+#[test]
+fn esp32s2_decoding() {
+    // entry: 14 bit page number, bit 14 = invalid, bit 15 = flash (otherwise PSRAM)
+    let mut mmu = Mmu::new(1 << 14);
+    mmu.set_entry(0, 128, (1 << 15) | 0x01);
+    mmu.set_entry(0, 11, (1 << 15) | 0x04);
+    mmu.set_entry(0, 256, 0x01);
+
+    // DROM0 uses IBUS2 entries (from 128)
+    assert_eq!(Esp32s2::get_entry_id(&mmu, 0, 0x3F00_0020), 128);
+    assert_eq!(Esp32s2::vaddr_to_paddr(&mmu, 0, 0x3F00_0020), Some(0x10020));
+    assert_eq!(Esp32s2::vaddr_to_paddr(&mmu, 0, 0x400B_1000), Some(0x41000));
+    // DRAM1 (DBUS1, entries from 256) mapped to PSRAM
+    assert_eq!(Esp32s2::get_entry_id(&mmu, 0, 0x3F80_0000), 256);
+    assert_eq!(Esp32s2::vaddr_to_paddr(&mmu, 0, 0x3F80_0000), None);
+}
+
+#[test]
+fn esp32c6_h2_decoding() {
+    fn check<C: MmuLl>() {
+        // entry: 9 bit page number, bit 9 = valid, page size is configurable
+        let mut mmu = Mmu::new(0);
+        mmu.set_entry(0, 3, (1 << 9) | 0x05);
+        mmu.set_entry(0, 4, 0x05);
+
+        assert_eq!(C::get_page_size(&mmu, 0), 0x10000);
+        assert_eq!(C::vaddr_to_paddr(&mmu, 0, 0x4203_0010), Some(0x50010));
+        assert_eq!(C::vaddr_to_paddr(&mmu, 0, 0x4204_0010), None);
+        assert!(!C::check_valid_ext_vaddr_region(&mmu, 0, 0x4300_0000));
+
+        mmu.page_size_code[0] = 1;
+        assert_eq!(C::get_page_size(&mmu, 0), MMU_PAGE_32KB);
+        assert_eq!(C::vaddr_to_paddr(&mmu, 0, 0x4201_8010), Some(0x28010));
+        assert!(!C::check_valid_ext_vaddr_region(&mmu, 0, 0x4280_0000));
+
+        mmu.page_size_code[0] = 3;
+        assert_eq!(C::get_page_size(&mmu, 0), MMU_PAGE_8KB);
+        assert_eq!(C::vaddr_to_paddr(&mmu, 0, 0x4200_6004), Some(0xA004));
+    }
+
+    check::<Esp32c6>();
+    check::<Esp32h2>();
+}
+
+#[test]
+fn esp32c2_decoding() {
+    // entry: 6 bit page number, bit 6 = invalid, 64 entries, page size is configurable
+    let mut mmu = Mmu::new(1 << 6);
+    mmu.set_entry(0, 2, 0x07);
+
+    assert_eq!(Esp32c2::get_page_size(&mmu, 0), MMU_PAGE_16KB);
+    assert_eq!(Esp32c2::vaddr_to_paddr(&mmu, 0, 0x4200_8100), Some(0x1C100));
+    assert_eq!(Esp32c2::vaddr_to_paddr(&mmu, 0, 0x4210_8100), None);
+
+    mmu.page_size_code[0] = 2;
+    assert_eq!(Esp32c2::vaddr_to_paddr(&mmu, 0, 0x3C02_0100), Some(0x70100));
+    assert!(Esp32c2::check_valid_ext_vaddr_region(&mmu, 0, 0x4210_8100));
+}
+
+#[test]
+fn esp32c5_decoding() {
+    // entry: 10 bit page number, bit 10 = PSRAM, bit 11 = valid, 512 entries
+    let mut mmu = Mmu::new(0);
+    mmu.set_entry(0, 1, (1 << 11) | 0x3FF);
+    mmu.set_entry(0, 2, (1 << 11) | (1 << 10) | 0x01);
+
+    assert_eq!(Esp32c5::get_mmu_id(0x4201_0000), 0);
+    assert_eq!(
+        Esp32c5::vaddr_to_paddr(&mmu, 0, 0x4201_0010),
+        Some(0x3FF_0010)
+    );
+    assert_eq!(Esp32c5::get_entry_target(&mmu, 0, 2), MmuTarget::Psram);
+    assert_eq!(Esp32c5::vaddr_to_paddr(&mmu, 0, 0x4202_0010), None);
+    assert!(Esp32c5::check_valid_ext_vaddr_region(&mmu, 0, 0x43FF_FFFF));
+    assert!(!Esp32c5::check_valid_ext_vaddr_region(&mmu, 0, 0x4400_0000));
+
+    mmu.page_size_code[0] = 2;
+    assert_eq!(Esp32c5::get_page_size(&mmu, 0), MMU_PAGE_16KB);
+    assert_eq!(
+        Esp32c5::vaddr_to_paddr(&mmu, 0, 0x4200_4004),
+        Some(0xFFC004)
+    );
+    assert!(!Esp32c5::check_valid_ext_vaddr_region(&mmu, 0, 0x4280_0000));
+}
+
+#[test]
+fn esp32c61_decoding() {
+    // entry: 9 bit page number, bit 9 = PSRAM, bit 10 = valid, 256 entries
+    let mut mmu = Mmu::new(0);
+    mmu.set_entry(0, 1, (1 << 10) | 0x1FF);
+    mmu.set_entry(0, 2, (1 << 10) | (1 << 9) | 0x01);
+    // valid on C5, but not on C61
+    mmu.set_entry(0, 3, (1 << 11) | 0x01);
+
+    assert_eq!(Esp32c61::get_mmu_id(0x4201_0000), 0);
+    assert_eq!(
+        Esp32c61::vaddr_to_paddr(&mmu, 0, 0x4201_0010),
+        Some(0x1FF_0010)
+    );
+    assert_eq!(Esp32c61::get_entry_target(&mmu, 0, 2), MmuTarget::Psram);
+    assert_eq!(Esp32c61::vaddr_to_paddr(&mmu, 0, 0x4202_0010), None);
+    assert_eq!(Esp32c61::vaddr_to_paddr(&mmu, 0, 0x4203_0010), None);
+    assert!(Esp32c61::check_valid_ext_vaddr_region(&mmu, 0, 0x42FF_FFFF));
+    assert!(!Esp32c61::check_valid_ext_vaddr_region(
+        &mmu,
+        0,
+        0x4300_0000
+    ));
+
+    mmu.page_size_code[0] = 2;
+    assert_eq!(Esp32c61::get_page_size(&mmu, 0), MMU_PAGE_16KB);
+    assert_eq!(
+        Esp32c61::vaddr_to_paddr(&mmu, 0, 0x4200_4004),
+        Some(0x7FC004)
+    );
+    assert!(!Esp32c61::check_valid_ext_vaddr_region(
+        &mmu,
+        0,
+        0x4240_0000
+    ));
+}
+
+#[test]
+fn esp32p4_decoding() {
+    // separate MMUs: flash (id 0, bit 12 = valid), PSRAM (id 1, bit 11 = valid)
+    let mut mmu = Mmu::new(0);
+    mmu.set_entry(0, 1, (1 << 12) | 0x12);
+    mmu.set_entry(1, 1, (1 << 11) | 0x12);
+
+    assert_eq!(Esp32p4::get_mmu_id(0x4001_0000), 0);
+    assert_eq!(Esp32p4::get_mmu_id(0x4801_0000), 1);
+    assert_eq!(
+        Esp32p4::vaddr_to_paddr(&mmu, 0, 0x4001_0020),
+        Some(0x12_0020)
+    );
+    assert_eq!(Esp32p4::get_entry_id(&mmu, 1, 0x4801_0020), 1);
+    assert!(Esp32p4::check_entry_valid(&mmu, 1, 1));
+    assert_eq!(Esp32p4::get_entry_target(&mmu, 1, 1), MmuTarget::Psram);
+    assert_eq!(Esp32p4::vaddr_to_paddr(&mmu, 1, 0x4801_0020), None);
+    // vaddr of other MMU
+    assert_eq!(Esp32p4::vaddr_to_paddr(&mmu, 0, 0x4801_0020), None);
+    assert_eq!(Esp32p4::vaddr_to_paddr(&mmu, 0, 0x4002_0000), None);
+}
+
+#[test]
+fn wrong_page_size() {
+    assert_eq!(
+        mmu_hal::vaddr_to_paddr_with(0x4200_0000, 0, |_| 0, |_| Some(0)),
+        None
+    );
+}
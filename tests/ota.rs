@@ -0,0 +1,203 @@
+mod common;
+
+use common::*;
+use esp_hal_ota::{OTA_SIZE_UNKNOWN, Ota, OtaError, OtaImgState};
+
+#[test]
+fn full_update() {
+    let image = firmware(10_000);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    for chunk in image.chunks(1000) {
+        ota.ota_write_chunk(chunk).unwrap();
+    }
+
+    assert!(ota.ota_verify().unwrap());
+    ota.ota_flush(true, true).unwrap();
+}
+
+#[test]
+fn wrong_crc() {
+    let image = firmware(4096);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+
+    ota.ota_begin(image.len() as u32, 0xDEADBEEF).unwrap();
+    assert_eq!(ota.ota_write_chunk(&image), Ok(true));
+    assert_eq!(
+        ota.ota_flush(false, true),
+        Err(OtaError::WrongCRC {
+            expected: 0xDEADBEEF,
+            calculated: crc(&image),
+        })
+    );
+}
+
+#[test]
+fn begin_rejects_image_larger_than_slot() {
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+
+    assert_eq!(
+        ota.ota_begin(OTA_SIZE + 1, 0),
+        Err(OtaError::ImageTooLarge {
+            image_size: OTA_SIZE + 1,
+            slot_size: OTA_SIZE,
+        })
+    );
+    assert!(ota.ota_begin(OTA_SIZE, 0).is_ok());
+}
+
+#[test]
+fn resume_validates_progress() {
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+
+    // caller "resumes" an update that is bigger than the slot
+    assert_eq!(
+        ota.ota_resume(OTA_SIZE + 0x1000, 0x2000, 0, 0),
+        Err(OtaError::ImageTooLarge {
+            image_size: OTA_SIZE + 0x1000,
+            slot_size: OTA_SIZE,
+        })
+    );
+    assert_eq!(
+        ota.ota_resume(0x1000, 0x2000, 0, 0),
+        Err(OtaError::InvalidResumeProgress {
+            flash_size: 0x1000,
+            remaining: 0x2000,
+        })
+    );
+    assert_eq!(ota.ota_write_chunk(&[0; 16]), Err(OtaError::OtaNotStarted));
+}
+
+#[test]
+fn resume_continues_at_offset() {
+    let image = firmware(8192);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image[..5000]).unwrap();
+    let (remaining, last_crc) = ota.get_progress_details().unwrap();
+    // progress only covers whole sectors written to flash
+    let written = image.len() - remaining as usize;
+    assert_eq!(written, 4096);
+
+    ota.ota_resume(image.len() as u32, remaining, crc(&image), last_crc)
+        .unwrap();
+    assert_eq!(ota.ota_write_chunk(&image[written..]), Ok(true));
+    ota.ota_flush(true, false).unwrap();
+}
+
+#[test]
+fn unknown_size_update() {
+    let image = firmware(9000);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+
+    ota.ota_begin(OTA_SIZE_UNKNOWN, 0).unwrap();
+    for chunk in image.chunks(1024) {
+        assert_eq!(ota.ota_write_chunk(chunk), Ok(false));
+    }
+
+    assert_eq!(ota.ota_flush(true, true), Err(OtaError::OtaNotFinished));
+    assert_eq!(
+        ota.ota_finish(image.len() as u32 + 1, crc(&image)),
+        Err(OtaError::ImageSizeMismatch {
+            expected: image.len() as u32 + 1,
+            written: image.len() as u32,
+        })
+    );
+
+    ota.ota_finish(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_flush(true, true).unwrap();
+}
+
+#[test]
+fn unknown_size_update_wrong_digest() {
+    let image = firmware(2000);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+
+    ota.ota_begin(OTA_SIZE_UNKNOWN, 0).unwrap();
+    ota.ota_write_chunk(&image).unwrap();
+    ota.ota_finish(image.len() as u32, 1234).unwrap();
+    assert_eq!(
+        ota.ota_flush(false, true),
+        Err(OtaError::WrongCRC {
+            expected: 1234,
+            calculated: crc(&image),
+        })
+    );
+}
+
+#[test]
+fn unknown_size_update_cannot_overflow_slot() {
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+
+    ota.ota_begin(OTA_SIZE_UNKNOWN, 0).unwrap();
+    let chunk = [0xA5; 0x1000];
+    for _ in 0..OTA_SIZE / 0x1000 {
+        assert_eq!(ota.ota_write_chunk(&chunk), Ok(false));
+    }
+
+    assert_eq!(
+        ota.ota_write_chunk(&chunk[..1]),
+        Err(OtaError::ImageTooLarge {
+            image_size: OTA_SIZE + 1,
+            slot_size: OTA_SIZE,
+        })
+    );
+    assert!(
+        ota.ota_finish(OTA_SIZE, crc(&[0xA5; OTA_SIZE as usize]))
+            .is_ok()
+    );
+}
+
+#[test]
+fn abort_running_update() {
+    let image = firmware(3 * 4096 + 100);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image[..2 * 4096 + 10]).unwrap();
+    ota.ota_abort(true).unwrap();
+
+    assert_eq!(ota.ota_write_chunk(&image), Err(OtaError::OtaNotStarted));
+    assert_eq!(ota.ota_resume_from_journal(), Ok(None));
+    assert_eq!(
+        flash.slice(ota_offset(0), image.len()),
+        vec![0xFF; image.len()]
+    );
+
+    // abort is idempotent
+    ota.ota_abort(true).unwrap();
+}
+
+#[test]
+fn abort_flushed_update() {
+    let image = firmware(5000);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image).unwrap();
+    ota.ota_flush(true, true).unwrap();
+
+    let (slot1, _) = ota.get_ota_boot_entries().unwrap();
+    assert_eq!(slot1.seq, 1);
+    assert_eq!(slot1.ota_state, OtaImgState::EspOtaImgNew);
+
+    ota.ota_abort(false).unwrap();
+    let (slot1, _) = ota.get_ota_boot_entries().unwrap();
+    assert_eq!(slot1.ota_state, OtaImgState::EspOtaImgAborted);
+
+    // data stays if not erased
+    assert_eq!(flash.slice(ota_offset(0), image.len()), image);
+}
+
+#[test]
+fn rollback_needs_running_partition() {
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+    assert_eq!(
+        ota.ota_rollback(),
+        Err(OtaError::CannotFindCurrentBootPartition)
+    );
+}
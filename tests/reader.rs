@@ -0,0 +1,66 @@
+mod common;
+
+use common::*;
+use embedded_io::Read;
+use esp_hal_ota::{Ota, OtaError};
+
+#[test]
+fn image_reader_reads_exact_image() {
+    let image = app_image(
+        &[
+            (0x3C00_0020, &firmware(3000)),
+            (0x4200_0020, &firmware(777)),
+        ],
+        true,
+    );
+    let flash = MockFlash::new();
+    install_image(&flash, 1, &image);
+
+    let mut ota = Ota::new(flash).unwrap();
+    let mut reader = ota.image_reader(1).unwrap();
+    assert_eq!(reader.size(), image.len() as u32);
+
+    let mut out = Vec::new();
+    let mut buf = [0; 1000];
+    loop {
+        let n = reader.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+
+        out.extend_from_slice(&buf[..n]);
+    }
+
+    assert_eq!(out, image);
+    assert_eq!(reader.remaining(), 0);
+}
+
+#[test]
+fn image_reader_read_exact() {
+    let image = app_image(&[(0x3C00_0020, &firmware(100))], false);
+    let flash = MockFlash::new();
+    install_image(&flash, 0, &image);
+
+    let mut ota = Ota::new(flash).unwrap();
+    let mut reader = ota.image_reader(0).unwrap();
+
+    let mut head = [0; 24];
+    reader.read_exact(&mut head).unwrap();
+    assert_eq!(head, image[..24]);
+
+    let mut rest = vec![0; image.len()];
+    assert!(reader.read_exact(&mut rest).is_err());
+}
+
+#[test]
+fn image_reader_needs_valid_image() {
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+    assert!(matches!(
+        ota.image_reader(0),
+        Err(OtaError::InvalidImage { partition: 0 })
+    ));
+    assert!(matches!(
+        ota.image_reader(5),
+        Err(OtaError::InvalidPartition { partition: 5 })
+    ));
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::*;
+use esp_hal_ota::{
+    BootPartitionSource, Ota, OtaImgState,
+    supervisor::{RollbackSupervisor, SupervisorOutcome},
+};
+
+fn small_image() -> Vec<u8> {
+    app_image(&[(0x3C00_0020, &firmware(256))], false)
+}
+
+/// ota_0 contains previous (valid) image, ota_1 the new one booted for the first time
+fn updated_flash() -> MockFlash {
+    let flash = MockFlash::new();
+    flash.write_raw(ota_offset(0), &[0xE9]);
+    flash.write_raw(ota_offset(1), &[0xE9]);
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.set_target_ota_boot_partition(0, OtaImgState::EspOtaImgValid)
+        .unwrap();
+    ota.set_target_ota_boot_partition(1, OtaImgState::EspOtaImgNew)
+        .unwrap();
+
+    flash
+}
+
+#[test]
+fn running_partition_falls_back_to_otadata() {
+    let flash = MockFlash::new();
+    assert_eq!(
+        Ota::new(flash.clone()).unwrap().get_running_partition(),
+        None
+    );
+
+    install_image(&flash, 1, &small_image());
+    Ota::new(flash.clone())
+        .unwrap()
+        .switch_to(1, false)
+        .unwrap();
+    assert_eq!(
+        Ota::new(flash.clone()).unwrap().get_running_partition(),
+        Some((1, BootPartitionSource::Otadata))
+    );
+
+    // bootloader skips invalid entries
+    let flash = updated_flash();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    assert_eq!(ota.get_currently_booted_partition(), Some(1));
+    ota.ota_mark_app_invalid_rollback().unwrap();
+    assert_eq!(
+        Ota::new(flash).unwrap().get_currently_booted_partition(),
+        Some(0)
+    );
+}
+
+#[test]
+fn running_partition_needs_image() {
+    let flash = MockFlash::new();
+    install_image(&flash, 1, &small_image());
+    Ota::new(flash.clone())
+        .unwrap()
+        .switch_to(1, false)
+        .unwrap();
+    flash.write_raw(ota_offset(1), &[0xFF]);
+
+    assert_eq!(Ota::new(flash).unwrap().get_running_partition(), None);
+}
+
+#[test]
+fn rollback_to_previous_image() {
+    let flash = updated_flash();
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    assert_eq!(ota.get_currently_booted_partition(), Some(1));
+    assert_eq!(ota.get_ota_image_state(), Ok(OtaImgState::EspOtaImgNew));
+    assert_eq!(ota.ota_rollback(), Ok(0));
+
+    let mut ota = Ota::new(flash).unwrap();
+    assert_eq!(ota.get_currently_booted_partition(), Some(0));
+    assert_eq!(ota.get_ota_image_state(), Ok(OtaImgState::EspOtaImgValid));
+}
+
+#[test]
+fn supervisor_marks_healthy_image_valid() {
+    let flash = updated_flash();
+    let supervisor = RollbackSupervisor::new(3, 100);
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    let mut calls = 0;
+    let mut check = || {
+        calls += 1;
+        calls > 2
+    };
+    let outcome = supervisor.run(&mut ota, &mut [&mut check], || 0).unwrap();
+    assert_eq!(outcome, SupervisorOutcome::MarkedValid);
+
+    let mut ota = Ota::new(flash).unwrap();
+    assert_eq!(ota.get_ota_image_state(), Ok(OtaImgState::EspOtaImgValid));
+    assert_eq!(
+        supervisor.run(&mut ota, &mut [], || 0),
+        Ok(SupervisorOutcome::NotInTrial(OtaImgState::EspOtaImgValid))
+    );
+}
+
+#[test]
+fn supervisor_rolls_back_after_failed_trials() {
+    let flash = updated_flash();
+    let supervisor = RollbackSupervisor::new(2, 10);
+
+    let mut outcomes = Vec::new();
+    for _ in 0..2 {
+        // every iteration is one boot
+        let mut ota = Ota::new(flash.clone()).unwrap();
+        let mut now = 0;
+        let mut failing = || false;
+        outcomes.push(
+            supervisor
+                .run(&mut ota, &mut [&mut failing], || {
+                    now += 5;
+                    now
+                })
+                .unwrap(),
+        );
+    }
+
+    assert_eq!(
+        outcomes,
+        [
+            SupervisorOutcome::RetryTrial {
+                attempt: 1,
+                max_attempts: 2
+            },
+            SupervisorOutcome::RolledBack { partition: 0 },
+        ]
+    );
+    assert_eq!(
+        Ota::new(flash).unwrap().get_currently_booted_partition(),
+        Some(0)
+    );
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::*;
+use esp_hal_ota::{OTA_SIZE_UNKNOWN, Ota, WriteStats};
+
+/// Writes image in `chunk_size` chunks, returns flash operations done on target partition
+fn update(image: &[u8], chunk_size: usize, size: u32) -> FlashStats {
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.ota_begin(size, crc(image)).unwrap();
+    flash.take_stats();
+    for chunk in image.chunks(chunk_size) {
+        ota.ota_write_chunk(chunk).unwrap();
+    }
+
+    if size == OTA_SIZE_UNKNOWN {
+        ota.ota_finish(image.len() as u32, crc(image)).unwrap();
+    }
+
+    let stats = flash.take_stats();
+    ota.ota_flush(true, true).unwrap();
+    assert_eq!(flash.slice(ota_offset(0), image.len()), image);
+
+    stats
+}
+
+#[test]
+fn every_sector_is_erased_once() {
+    let image = firmware(10 * SECTOR_SIZE as usize + 1234);
+    let sectors = image.len().div_ceil(SECTOR_SIZE as usize);
+
+    for size in [image.len() as u32, OTA_SIZE_UNKNOWN] {
+        for chunk_size in [1, 100, 1000, 4096, 5000, 16384, image.len()] {
+            let stats = update(&image, chunk_size, size);
+
+            assert_eq!(stats.writes, sectors, "chunk size {chunk_size}");
+            for sector in 0..sectors as u32 {
+                let offset = ota_offset(0) + sector * SECTOR_SIZE;
+                assert_eq!(stats.erases_in(offset, SECTOR_SIZE), 1);
+            }
+        }
+    }
+}
+
+#[test]
+fn buffered_tail_is_written_on_flush() {
+    let image = firmware(SECTOR_SIZE as usize + 100);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.ota_begin(OTA_SIZE_UNKNOWN, 0).unwrap();
+    assert_eq!(ota.ota_write_chunk(&image), Ok(false));
+    assert_eq!(
+        flash.slice(ota_offset(0) + SECTOR_SIZE, 100),
+        vec![0xFF; 100]
+    );
+    assert_eq!(
+        ota.get_progress_details(),
+        Some((OTA_SIZE - SECTOR_SIZE, crc(&image[..SECTOR_SIZE as usize])))
+    );
+
+    ota.ota_finish(image.len() as u32, crc(&image)).unwrap();
+    assert_eq!(flash.slice(ota_offset(0), image.len()), image);
+    ota.ota_flush(true, true).unwrap();
+}
+
+#[test]
+fn abort_drops_buffered_data() {
+    let image = firmware(SECTOR_SIZE as usize + 100);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image[..SECTOR_SIZE as usize + 50])
+        .unwrap();
+    ota.ota_abort(false).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    assert_eq!(ota.ota_write_chunk(&image), Ok(true));
+    ota.ota_flush(true, true).unwrap();
+}
+
+#[test]
+fn compare_before_write_skips_same_sectors() {
+    let image = firmware(10 * SECTOR_SIZE as usize + 1234);
+    let sectors = image.len().div_ceil(SECTOR_SIZE as usize) as u32;
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image).unwrap();
+    assert_eq!(
+        ota.get_write_stats(),
+        Some(WriteStats {
+            sectors_written: sectors,
+            sectors_skipped: 0,
+            bytes_skipped: 0,
+        })
+    );
+    ota.ota_flush(true, true).unwrap();
+
+    // same build again, only third sector differs
+    let mut update = image.clone();
+    update[2 * SECTOR_SIZE as usize + 10] ^= 0xFF;
+    ota.set_compare_before_write(true);
+    ota.ota_begin(update.len() as u32, crc(&update)).unwrap();
+    flash.take_stats();
+    for chunk in update.chunks(1000) {
+        ota.ota_write_chunk(chunk).unwrap();
+    }
+
+    let stats = flash.take_stats();
+    assert_eq!(stats.writes, 1);
+    assert_eq!(
+        stats.erases_in(ota_offset(0) + 2 * SECTOR_SIZE, SECTOR_SIZE),
+        1
+    );
+    assert_eq!(
+        ota.get_write_stats(),
+        Some(WriteStats {
+            sectors_written: 1,
+            sectors_skipped: sectors - 1,
+            bytes_skipped: image.len() as u32 - SECTOR_SIZE,
+        })
+    );
+
+    ota.ota_flush(true, true).unwrap();
+    assert_eq!(flash.slice(ota_offset(0), update.len()), update);
+}
+
+#[test]
+fn compare_before_write_random_access() {
+    let image = firmware(4 * SECTOR_SIZE as usize);
+    let flash = MockFlash::new();
+    install_image(&flash, 0, &image[..2 * SECTOR_SIZE as usize]);
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.set_compare_before_write(true);
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    flash.take_stats();
+
+    ota.ota_write_at(SECTOR_SIZE * 2, &image[2 * SECTOR_SIZE as usize..])
+        .unwrap();
+    assert_eq!(
+        ota.ota_write_at(0, &image[..2 * SECTOR_SIZE as usize]),
+        Ok(true)
+    );
+    assert_eq!(flash.take_stats().writes, 2);
+
+    let stats = ota.get_write_stats().unwrap();
+    assert_eq!((stats.sectors_written, stats.sectors_skipped), (2, 2));
+    ota.ota_flush(false, true).unwrap();
+}
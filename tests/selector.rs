@@ -0,0 +1,203 @@
+mod common;
+
+use common::*;
+use esp_hal_ota::{
+    Ota, OtaError, OtaImgState,
+    selector::{SlotContext, SlotSelector, SlotStrategy},
+};
+
+fn context(running: Option<usize>, entries: &[(usize, u32, OtaImgState)]) -> SlotContext {
+    let mut otadata = [None; 16];
+    for &(partition, seq, state) in entries {
+        otadata[partition] = Some((seq, state));
+    }
+
+    SlotContext {
+        count: 4,
+        running,
+        otadata,
+    }
+}
+
+#[test]
+fn round_robin_skips_running() {
+    let mut strategy = SlotStrategy::RoundRobin;
+    assert_eq!(strategy.select(&context(None, &[])), Some(0));
+    assert_eq!(strategy.select(&context(Some(1), &[])), Some(2));
+    assert_eq!(strategy.select(&context(Some(3), &[])), Some(0));
+}
+
+#[test]
+fn oldest_seq_prefers_unused_partitions() {
+    let ctx = context(
+        Some(2),
+        &[
+            (1, 6, OtaImgState::EspOtaImgValid),
+            (2, 7, OtaImgState::EspOtaImgValid),
+        ],
+    );
+    assert_eq!(SlotStrategy::OldestSeq.select(&ctx), Some(3));
+
+    let mut ctx = context(Some(2), &[(2, 7, OtaImgState::EspOtaImgValid)]);
+    ctx.count = 3;
+    ctx.otadata[0] = Some((5, OtaImgState::EspOtaImgValid));
+    ctx.otadata[1] = Some((3, OtaImgState::EspOtaImgValid));
+    assert_eq!(SlotStrategy::OldestSeq.select(&ctx), Some(1));
+}
+
+#[test]
+fn keep_last_valid_never_overwrites_fallback() {
+    // running ota_3 in trial, ota_0 is last valid image
+    let ctx = context(
+        Some(3),
+        &[
+            (0, 5, OtaImgState::EspOtaImgValid),
+            (3, 8, OtaImgState::EspOtaImgPendingVerify),
+        ],
+    );
+    assert_eq!(ctx.last_valid(), Some(0));
+    assert_eq!(SlotStrategy::RoundRobin.select(&ctx), Some(0));
+    assert_eq!(SlotStrategy::KeepLastValid.select(&ctx), Some(1));
+
+    let mut ctx = context(Some(1), &[(0, 5, OtaImgState::EspOtaImgValid)]);
+    ctx.count = 2;
+    assert_eq!(SlotStrategy::KeepLastValid.select(&ctx), None);
+}
+
+#[test]
+fn slot_context_from_otadata() {
+    let flash = MockFlash::with_ota_partitions(4);
+    for idx in [1, 2] {
+        install_image(
+            &flash,
+            idx,
+            &app_image(&[(0x3C00_0020, &firmware(64))], false),
+        );
+    }
+
+    let mut ota = Ota::new(flash).unwrap();
+    ota.switch_to(2, false).unwrap();
+    ota.switch_to(1, true).unwrap();
+
+    let ctx = ota.slot_context().unwrap();
+    assert_eq!(ctx.count, 4);
+    assert_eq!(ctx.otadata[1], Some((6, OtaImgState::EspOtaImgNew)));
+    assert_eq!(ctx.otadata[2], Some((3, OtaImgState::EspOtaImgUndefined)));
+    assert_eq!(ctx.last_valid(), Some(2));
+}
+
+#[test]
+fn ota_begin_uses_slot_strategy() {
+    let image = firmware(0x1800);
+    let flash = MockFlash::with_ota_partitions(3);
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.set_slot_strategy(SlotStrategy::Explicit(2));
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image).unwrap();
+    assert_eq!(flash.slice(ota_offset(2), image.len()), image);
+
+    ota.set_slot_strategy(SlotStrategy::Explicit(3));
+    assert_eq!(
+        ota.ota_begin(image.len() as u32, crc(&image)),
+        Err(OtaError::InvalidPartition { partition: 3 })
+    );
+
+    let mut none = |_: &SlotContext| None;
+    assert_eq!(ota.select_slot(&mut none), Err(OtaError::NoSlotAvailable));
+}
+
+#[test]
+fn ota_begin_in_targets_partition() {
+    let image = firmware(0x1234);
+    let flash = MockFlash::with_ota_partitions(3);
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.ota_begin_in(1, image.len() as u32, crc(&image))
+        .unwrap();
+    assert!(ota.ota_write_chunk(&image).unwrap());
+    ota.ota_flush(true, false).unwrap();
+
+    assert_eq!(flash.slice(ota_offset(1), image.len()), image);
+    assert_eq!(
+        ota.slot_context().unwrap().otadata[1],
+        Some((2, OtaImgState::EspOtaImgUndefined))
+    );
+}
+
+#[test]
+fn ota_begin_with_custom_selector() {
+    let image = firmware(0x1800);
+    let flash = MockFlash::with_ota_partitions(4);
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    let mut last = |ctx: &SlotContext| ctx.candidates().max();
+    ota.ota_begin_with(&mut last, image.len() as u32, crc(&image))
+        .unwrap();
+    assert!(ota.ota_write_chunk(&image).unwrap());
+    assert_eq!(flash.slice(ota_offset(3), image.len()), image);
+
+    let mut none = |_: &SlotContext| None;
+    assert_eq!(
+        ota.ota_begin_with(&mut none, image.len() as u32, crc(&image)),
+        Err(OtaError::NoSlotAvailable)
+    );
+}
+
+#[test]
+fn ota_resume_refuses_running_partition() {
+    let flash = MockFlash::new();
+    install_image(
+        &flash,
+        1,
+        &app_image(&[(0x3C00_0020, &firmware(64))], false),
+    );
+    Ota::new(flash.clone())
+        .unwrap()
+        .switch_to(1, false)
+        .unwrap();
+
+    let mut ota = Ota::new(flash).unwrap();
+    ota.set_slot_strategy(SlotStrategy::Explicit(1));
+    assert_eq!(
+        ota.ota_resume(0x2000, 0x1000, 0, 0),
+        Err(OtaError::PartitionRunning { partition: 1 })
+    );
+    assert_eq!(
+        ota.ota_resume_in(1, 0x2000, 0x1000, 0, 0),
+        Err(OtaError::PartitionRunning { partition: 1 })
+    );
+}
+
+#[test]
+fn ota_resume_in_keeps_explicit_partition() {
+    let image = firmware(8192);
+    let flash = MockFlash::with_ota_partitions(3);
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.ota_begin_in(2, image.len() as u32, crc(&image))
+        .unwrap();
+    ota.ota_write_chunk(&image[..5000]).unwrap();
+    let (remaining, last_crc) = ota.get_progress_details().unwrap();
+    let partition = ota.get_target_partition().unwrap();
+    assert_eq!(partition, 2);
+    drop(ota);
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.ota_resume_in(
+        partition,
+        image.len() as u32,
+        remaining,
+        crc(&image),
+        last_crc,
+    )
+    .unwrap();
+    let written = image.len() - remaining as usize;
+    assert_eq!(ota.ota_write_chunk(&image[written..]), Ok(true));
+    ota.ota_flush(true, false).unwrap();
+
+    assert_eq!(flash.slice(ota_offset(2), image.len()), image);
+    // seq 3 selects third OTA partition
+    let (slot1, _) = ota.get_ota_boot_entries().unwrap();
+    assert_eq!(slot1.seq, 3);
+}
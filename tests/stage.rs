@@ -0,0 +1,111 @@
+mod common;
+
+use common::*;
+use esp_hal_ota::{Ota, OtaError, OtaImgState, StagedImage, journal::JOURNAL_SIZE};
+
+#[test]
+fn stage_then_activate() {
+    let image = firmware(20_000);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image).unwrap();
+    assert_eq!(ota.ota_stage(true), Ok(0));
+
+    // otadata is untouched
+    assert_eq!(
+        flash.slice(OTADATA_OFFSET, OTADATA_SIZE as usize),
+        vec![0xFF; OTADATA_SIZE as usize]
+    );
+    assert_eq!(
+        ota.get_staged_image(),
+        Ok(Some(StagedImage {
+            partition: 0,
+            size: image.len() as u32,
+            crc: crc(&image),
+        }))
+    );
+
+    assert_eq!(
+        ota.ota_activate(1, true),
+        Err(OtaError::NotStaged { partition: 1 })
+    );
+    ota.ota_activate(0, true).unwrap();
+
+    let (slot1, _) = ota.get_ota_boot_entries().unwrap();
+    assert_eq!(slot1.seq, 1);
+    assert_eq!(slot1.ota_state, OtaImgState::EspOtaImgNew);
+    assert_eq!(ota.get_staged_image(), Ok(None));
+}
+
+#[test]
+fn activate_rejects_corrupted_image() {
+    let image = firmware(5000);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image).unwrap();
+    ota.ota_stage(false).unwrap();
+
+    flash.write_raw(ota_offset(0) + 1234, &[0]);
+    assert!(matches!(
+        ota.ota_activate(0, false),
+        Err(OtaError::OtaVerifyError { .. })
+    ));
+}
+
+#[test]
+fn staged_image_survives_reset_with_journal() {
+    let image = firmware(5000);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image).unwrap();
+    ota.ota_stage(true).unwrap();
+    drop(ota);
+
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+    assert_eq!(
+        ota.get_staged_image().unwrap().map(|s| s.partition),
+        Some(0)
+    );
+    ota.ota_activate(0, false).unwrap();
+
+    let (slot1, _) = ota.get_ota_boot_entries().unwrap();
+    assert_eq!(slot1.ota_state, OtaImgState::EspOtaImgUndefined);
+    assert_eq!(ota.ota_resume_from_journal(), Ok(None));
+}
+
+#[test]
+fn abort_discards_staged_image() {
+    let image = firmware(5000);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image).unwrap();
+    ota.ota_stage(true).unwrap();
+    ota.ota_abort(false).unwrap();
+
+    assert_eq!(ota.get_staged_image(), Ok(None));
+}
+
+#[test]
+fn switch_to_discards_staged_image() {
+    let image = app_image(&[(0x3C00_0020, &firmware(5000))], false);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    ota.ota_write_chunk(&image).unwrap();
+    ota.ota_stage(true).unwrap();
+
+    ota.switch_to(0, false).unwrap();
+    assert_eq!(ota.get_staged_image(), Ok(None));
+    assert_eq!(
+        flash.slice(JOURNAL_OFFSET, JOURNAL_SIZE as usize),
+        vec![0xFF; JOURNAL_SIZE as usize]
+    );
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use common::*;
+use esp_hal_ota::{EspAppDesc, Ota, OtaImgState};
+
+fn image_with_version(version: &str) -> Vec<u8> {
+    let mut desc = EspAppDesc {
+        magic: EspAppDesc::MAGIC,
+        secure_version: 0,
+        version: [0; 32],
+        project_name: [0; 32],
+        time: [0; 16],
+        date: [0; 16],
+        idf_ver: [0; 32],
+        app_elf_sha256: [0; 32],
+    };
+    desc.version[..version.len()].copy_from_slice(version.as_bytes());
+    desc.project_name[..4].copy_from_slice(b"demo");
+
+    let mut drom = desc.to_bytes().to_vec();
+    drom.extend_from_slice(&firmware(500));
+    app_image(
+        &[(0x3C00_0020, &drom), (0x4200_0020, &firmware(2000))],
+        true,
+    )
+}
+
+#[test]
+fn app_desc_round_trip() {
+    let image = image_with_version("v1.2.3");
+    let offset = EspAppDesc::IMAGE_OFFSET as usize;
+    let bytes = image[offset..offset + EspAppDesc::SIZE].try_into().unwrap();
+
+    let desc = EspAppDesc::from_bytes(&bytes).unwrap();
+    assert_eq!(desc.version_str(), Some("v1.2.3"));
+    assert_eq!(desc.to_bytes(), bytes);
+    assert_eq!(EspAppDesc::from_bytes(&[0; EspAppDesc::SIZE]), None);
+}
+
+#[test]
+fn slots_status_of_empty_flash() {
+    let mut ota = Ota::new(MockFlash::with_ota_partitions(3)).unwrap();
+    let slots = ota.slots_status().collect::<Result<Vec<_>, _>>().unwrap();
+
+    assert_eq!(slots.len(), 3);
+    for (i, slot) in slots.iter().enumerate() {
+        assert_eq!(slot.partition, i);
+        assert_eq!(slot.offset, ota_offset(i));
+        assert_eq!(slot.size, OTA_SIZE);
+        assert_eq!(slot.label_str(), Some(format!("ota_{i}").as_str()));
+        assert!(!slot.running);
+        assert!(!slot.next_boot);
+        assert_eq!(slot.otadata, None);
+        assert!(!slot.image_valid);
+        assert_eq!(slot.app_version_str(), None);
+    }
+}
+
+#[test]
+fn slots_status_after_switch() {
+    let flash = MockFlash::with_ota_partitions(3);
+    install_image(&flash, 0, &image_with_version("1.0.0"));
+    install_image(&flash, 1, &image_with_version("1.1.0"));
+    // image without app description
+    install_image(&flash, 2, &app_image(&[(0x3C00_0020, &[0; 64])], false));
+
+    let mut ota = Ota::new(flash).unwrap();
+    ota.switch_to(0, false).unwrap();
+    ota.switch_to(1, true).unwrap();
+
+    let slots = ota.slots_status().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(
+        slots.iter().map(|s| s.next_boot).collect::<Vec<_>>(),
+        [false, true, false]
+    );
+    assert_eq!(slots[0].otadata, Some((1, OtaImgState::EspOtaImgUndefined)));
+    assert_eq!(slots[1].otadata, Some((2, OtaImgState::EspOtaImgNew)));
+    assert_eq!(slots[2].otadata, None);
+
+    assert!(slots.iter().all(|s| s.image_valid));
+    assert_eq!(slots[0].app_version_str(), Some("1.0.0"));
+    assert_eq!(slots[1].app_version_str(), Some("1.1.0"));
+    assert_eq!(slots[2].app_version_str(), None);
+
+    assert_eq!(ota.slot_status(1), Ok(slots[1].clone()));
+}
+
+#[test]
+fn next_boot_skips_invalid_and_missing_images() {
+    let flash = MockFlash::with_ota_partitions(3);
+    install_image(&flash, 0, &image_with_version("1.0.0"));
+    install_image(&flash, 1, &image_with_version("1.1.0"));
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    ota.switch_to(0, false).unwrap();
+    ota.switch_to(1, true).unwrap();
+    drop(ota);
+
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    assert_eq!(ota.get_currently_booted_partition(), Some(1));
+    ota.ota_mark_app_invalid_rollback().unwrap();
+
+    let next_boot = |ota: &mut Ota<MockFlash>| {
+        ota.slots_status()
+            .map(|s| s.unwrap().next_boot)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(next_boot(&mut ota), [true, false, false]);
+
+    // bootloader won't boot erased image either
+    flash.write_raw(ota_offset(0), &[0xFF]);
+    assert_eq!(next_boot(&mut ota), [false, false, false]);
+}
@@ -0,0 +1,106 @@
+use esp_hal_ota::{EspOtaSelectEntry, OtaImgState, OtaJournalRecord, PartitionEntry};
+
+#[test]
+fn ota_img_state_round_trip() {
+    for raw in [0x0, 0x1, 0x2, 0x3, 0x4, 0xFFFFFFFF, 0x5, 0xFFFF0002] {
+        let state = OtaImgState::from(raw);
+        assert_eq!(u32::from(state), raw);
+    }
+
+    assert_eq!(OtaImgState::from(0x2), OtaImgState::EspOtaImgValid);
+    assert_eq!(
+        OtaImgState::from(0xFFFF0002),
+        OtaImgState::Unknown(0xFFFF0002)
+    );
+}
+
+#[test]
+fn ota_select_entry_decode() {
+    let mut bytes = [0xFF; 32];
+    bytes[0..4].copy_from_slice(&3u32.to_le_bytes());
+    bytes[24..28].copy_from_slice(&0x2u32.to_le_bytes());
+    bytes[28..32].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+
+    let entry = EspOtaSelectEntry::from_bytes(&bytes);
+    assert_eq!(entry.seq, 3);
+    assert_eq!(entry.seq_label, [0xFF; 20]);
+    assert_eq!(entry.ota_state, OtaImgState::EspOtaImgValid);
+    assert_eq!(entry.crc, 0x1234_5678);
+    assert_eq!(entry.to_bytes(), bytes);
+}
+
+#[test]
+fn ota_select_entry_unknown_state_round_trip() {
+    // half-written state word
+    let mut bytes = [0xFF; 32];
+    bytes[24..28].copy_from_slice(&0xFFFF_0000u32.to_le_bytes());
+
+    let entry = EspOtaSelectEntry::from_bytes(&bytes);
+    assert_eq!(entry.ota_state, OtaImgState::Unknown(0xFFFF_0000));
+    assert_eq!(entry.to_bytes(), bytes);
+}
+
+#[test]
+fn ota_select_entry_crc_check() {
+    let seq = 5u32;
+    let mut entry = EspOtaSelectEntry {
+        seq,
+        seq_label: [0xFF; 20],
+        ota_state: OtaImgState::EspOtaImgNew,
+        crc: esp_hal_ota::crc32::calc_crc32(&seq.to_le_bytes(), 0xFFFFFFFF),
+    };
+
+    let mut decoded = EspOtaSelectEntry::from_bytes(&entry.to_bytes());
+    decoded.check_crc();
+    assert_eq!(decoded, entry);
+
+    entry.crc ^= 1;
+    entry.check_crc();
+    assert_eq!(entry.seq, 0);
+}
+
+#[test]
+fn partition_entry_round_trip() {
+    let mut label = [0; 16];
+    label[..5].copy_from_slice(b"ota_1");
+
+    let entry = PartitionEntry {
+        p_type: 0,
+        p_subtype: 0x11,
+        offset: 0x110000,
+        size: 0x100000,
+        label,
+        flags: 0,
+    };
+
+    let bytes = entry.to_bytes();
+    assert_eq!(bytes[0..2], PartitionEntry::MAGIC);
+    assert_eq!(PartitionEntry::from_bytes(&bytes), Some(entry.clone()));
+    assert_eq!(entry.label_str(), Some("ota_1"));
+}
+
+#[test]
+fn partition_entry_wrong_magic() {
+    let mut bytes = [0; 32];
+    bytes[0..2].copy_from_slice(&[0xEB, 0xEB]); // md5 entry
+    assert_eq!(PartitionEntry::from_bytes(&bytes), None);
+}
+
+#[test]
+fn journal_record_round_trip() {
+    let record = OtaJournalRecord {
+        target_partition: 1,
+        image_size: 0x12345,
+        target_crc: 0xCAFEBABE,
+        written: 0x10000,
+        last_crc: 0x01020304,
+    };
+
+    let mut bytes = record.to_bytes();
+    assert_eq!(OtaJournalRecord::from_bytes(&bytes), Some(record));
+
+    // torn write
+    bytes[16] ^= 0xFF;
+    assert_eq!(OtaJournalRecord::from_bytes(&bytes), None);
+    assert_eq!(OtaJournalRecord::from_bytes(&[0xFF; 32]), None);
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use common::*;
+use esp_hal_ota::{BlockBitmap, OTA_BLOCK_SIZE, Ota, OtaError, OtaJournalRecord};
+
+const BS: usize = OTA_BLOCK_SIZE as usize;
+
+#[test]
+fn out_of_order_update() {
+    let image = firmware(10 * BS + 123);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+
+    // two "peers" sending interleaved ranges, last one with partial block
+    assert_eq!(ota.ota_write_at(8 * BS as u32, &image[8 * BS..]), Ok(false));
+    assert_eq!(ota.ota_write_at(0, &image[..2 * BS]), Ok(false));
+    assert_eq!(
+        ota.ota_write_at(4 * BS as u32, &image[4 * BS..6 * BS]),
+        Ok(false)
+    );
+
+    let missing: Vec<_> = ota.missing_ranges().unwrap().collect();
+    assert_eq!(
+        missing,
+        vec![2 * BS as u32..4 * BS as u32, 6 * BS as u32..8 * BS as u32]
+    );
+    assert_eq!(
+        ota.ota_flush(false, true),
+        Err(OtaError::MissingBlocks {
+            offset: 2 * BS as u32
+        })
+    );
+
+    // duplicate block doesn't change progress
+    assert_eq!(ota.ota_write_at(0, &image[..BS]), Ok(false));
+    assert_eq!(
+        ota.ota_write_at(6 * BS as u32, &image[6 * BS..8 * BS]),
+        Ok(false)
+    );
+    assert_eq!(
+        ota.ota_write_at(2 * BS as u32, &image[2 * BS..4 * BS]),
+        Ok(true)
+    );
+
+    assert_eq!(ota.missing_ranges().unwrap().next(), None);
+    assert_eq!(ota.get_ota_progress(), 1.0);
+    ota.ota_flush(false, true).unwrap();
+}
+
+#[test]
+fn out_of_order_update_wrong_digest() {
+    let image = firmware(3 * BS);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+
+    let mut corrupted = image.clone();
+    corrupted[BS + 7] ^= 0xFF;
+    ota.ota_write_at(BS as u32, &corrupted[BS..]).unwrap();
+    ota.ota_write_at(0, &image[..BS]).unwrap();
+
+    assert_eq!(
+        ota.ota_flush(false, true),
+        Err(OtaError::WrongCRC {
+            expected: crc(&image),
+            calculated: crc(&corrupted),
+        })
+    );
+}
+
+#[test]
+fn unaligned_writes_are_rejected() {
+    let image = firmware(4 * BS + 10);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+
+    assert_eq!(
+        ota.ota_write_at(100, &image[100..BS]),
+        Err(OtaError::UnalignedWrite { offset: 100 })
+    );
+    assert_eq!(
+        ota.ota_write_at(0, &image[..BS + 1]),
+        Err(OtaError::UnalignedWrite { offset: 0 })
+    );
+    assert_eq!(
+        ota.ota_write_at(4 * BS as u32, &[0; BS]),
+        Err(OtaError::ImageTooLarge {
+            image_size: 5 * BS as u32,
+            slot_size: image.len() as u32,
+        })
+    );
+
+    ota.ota_write_at(0, &image[..BS]).unwrap();
+    assert_eq!(
+        ota.ota_write_chunk(&image),
+        Err(OtaError::RandomAccessInProgress)
+    );
+}
+
+#[test]
+fn writes_stay_in_target_slot() {
+    let flash = MockFlash::new();
+    // journaled update doesn't fit into ota_0 (e.g. partition table was changed)
+    let record = OtaJournalRecord {
+        target_partition: 0,
+        image_size: OTA_SIZE + 2 * BS as u32,
+        target_crc: 0,
+        written: 0,
+        last_crc: 0,
+    };
+    flash.write_raw(JOURNAL_OFFSET, &record.to_bytes());
+
+    let mut ota = Ota::new_with_journal(flash.clone(), JOURNAL_OFFSET).unwrap();
+    ota.ota_resume_from_journal().unwrap().unwrap();
+    assert_eq!(
+        ota.ota_write_at(OTA_SIZE, &[0; BS]),
+        Err(OtaError::ImageTooLarge {
+            image_size: OTA_SIZE + BS as u32,
+            slot_size: OTA_SIZE,
+        })
+    );
+    assert_eq!(flash.slice(ota_offset(1), BS), vec![0xFF; BS]);
+}
+
+#[test]
+fn sequential_writes_fill_bitmap() {
+    let image = firmware(3 * BS + 5);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+
+    ota.ota_write_chunk(&image[..BS + 10]).unwrap();
+    let missing: Vec<_> = ota.missing_ranges().unwrap().collect();
+    assert_eq!(missing, vec![BS as u32..image.len() as u32]);
+
+    // missing blocks can be filled in random order afterwards
+    ota.ota_write_at(2 * BS as u32, &image[2 * BS..]).unwrap();
+    assert_eq!(ota.ota_write_at(BS as u32, &image[BS..2 * BS]), Ok(true));
+    ota.ota_flush(true, true).unwrap();
+}
+
+#[test]
+fn bitmap_mark_prefix() {
+    let mut bitmap = BlockBitmap::new();
+    bitmap.mark_prefix(70 * OTA_BLOCK_SIZE + 1, 100 * OTA_BLOCK_SIZE);
+    assert_eq!(bitmap.count(), 70);
+    assert!(bitmap.is_set(69));
+    assert!(!bitmap.is_set(70));
+
+    bitmap.mark_prefix(100 * OTA_BLOCK_SIZE - 1, 100 * OTA_BLOCK_SIZE - 1);
+    assert_eq!(bitmap.count(), 100);
+    assert_eq!(bitmap.missing_ranges(100 * OTA_BLOCK_SIZE - 1).next(), None);
+}
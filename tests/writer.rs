@@ -0,0 +1,184 @@
+mod common;
+
+use common::*;
+use embedded_io::{ErrorKind, ErrorType, Read, Write};
+use esp_hal_ota::{OTA_SIZE_UNKNOWN, Ota, OtaError, OtaImgState};
+
+/// Reader that returns at most `chunk` bytes per read and fails after `fail_at` bytes
+struct ChunkedReader<'a> {
+    data: &'a [u8],
+    chunk: usize,
+    fail_at: Option<usize>,
+    pos: usize,
+}
+
+impl<'a> ChunkedReader<'a> {
+    fn new(data: &'a [u8], chunk: usize) -> Self {
+        Self {
+            data,
+            chunk,
+            fail_at: None,
+            pos: 0,
+        }
+    }
+}
+
+impl ErrorType for ChunkedReader<'_> {
+    type Error = ErrorKind;
+}
+
+impl Read for ChunkedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+        if self.fail_at.is_some_and(|fail| self.pos >= fail) {
+            return Err(ErrorKind::ConnectionReset);
+        }
+
+        let n = buf.len().min(self.chunk).min(self.data.len() - self.pos);
+        buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[test]
+fn writer_write_all() {
+    let image = firmware(10_000);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    let mut writer = ota.writer();
+    for chunk in image.chunks(777) {
+        writer.write_all(chunk).unwrap();
+    }
+    writer.flush().unwrap();
+
+    ota.ota_flush(true, true).unwrap();
+    assert_eq!(flash.slice(ota_offset(0), image.len()), image);
+}
+
+#[test]
+fn writer_stops_at_image_end() {
+    let image = firmware(5000);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+    assert_eq!(ota.writer().write(&image), Err(OtaError::OtaNotStarted));
+
+    ota.ota_begin(4000, crc(&image[..4000])).unwrap();
+    let mut writer = ota.writer();
+    assert_eq!(writer.write(&image[..3000]), Ok(3000));
+    assert_eq!(writer.write(&image[3000..]), Ok(1000));
+    assert_eq!(
+        writer.write(&image[4000..]),
+        Err(OtaError::ImageSizeMismatch {
+            expected: 4000,
+            written: 5000,
+        })
+    );
+
+    ota.ota_flush(true, false).unwrap();
+}
+
+#[test]
+fn update_from_reader_known_size() {
+    let image = firmware(10_000);
+    let mut stream = image.clone();
+    // reader mustn't be read past image
+    stream.extend_from_slice(b"trailer");
+
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+    let mut reader = ChunkedReader::new(&stream, 1500);
+    ota.update_from_reader(&mut reader, image.len() as u32, crc(&image), true, true)
+        .unwrap();
+
+    assert_eq!(reader.pos, image.len());
+    assert_eq!(flash.slice(ota_offset(0), image.len()), image);
+    let (slot1, _) = ota.get_ota_boot_entries().unwrap();
+    assert_eq!(slot1.ota_state, OtaImgState::EspOtaImgNew);
+}
+
+#[test]
+fn update_from_reader_unknown_size() {
+    let image = firmware(9000);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.update_from_reader(
+        &mut image.as_slice(),
+        OTA_SIZE_UNKNOWN,
+        crc(&image),
+        false,
+        false,
+    )
+    .unwrap();
+    assert_eq!(flash.slice(ota_offset(0), image.len()), image);
+    let (slot1, _) = ota.get_ota_boot_entries().unwrap();
+    assert_eq!(slot1.ota_state, OtaImgState::EspOtaImgUndefined);
+}
+
+#[test]
+fn update_from_reader_errors() {
+    let image = firmware(9000);
+    let mut ota = Ota::new(MockFlash::new()).unwrap();
+
+    assert_eq!(
+        ota.update_from_reader(
+            &mut &image[..8000],
+            image.len() as u32,
+            crc(&image),
+            true,
+            true
+        ),
+        Err(OtaError::ImageSizeMismatch {
+            expected: 9000,
+            written: 8000,
+        })
+    );
+    // update was aborted
+    assert_eq!(ota.get_progress_details(), None);
+
+    let mut reader = ChunkedReader::new(&image, 1000);
+    reader.fail_at = Some(5000);
+    assert_eq!(
+        ota.update_from_reader(&mut reader, image.len() as u32, crc(&image), true, true),
+        Err(OtaError::SourceReadError { offset: 5000 })
+    );
+    assert_eq!(ota.get_progress_details(), None);
+
+    assert_eq!(
+        ota.update_from_reader(&mut image.as_slice(), image.len() as u32, 1234, true, true),
+        Err(OtaError::OtaVerifyError {
+            expected: 1234,
+            calculated: crc(&image),
+        })
+    );
+}
+
+#[test]
+fn async_writer_and_reader() {
+    use embassy_futures::block_on;
+
+    let image = firmware(6000);
+    let flash = MockFlash::new();
+    let mut ota = Ota::new(flash.clone()).unwrap();
+
+    ota.ota_begin(image.len() as u32, crc(&image)).unwrap();
+    block_on(embedded_io_async::Write::write_all(
+        &mut ota.writer(),
+        &image,
+    ))
+    .unwrap();
+    ota.ota_flush(true, false).unwrap();
+
+    let update = firmware(7000);
+    block_on(ota.update_from_reader_async(
+        &mut update.as_slice(),
+        update.len() as u32,
+        crc(&update),
+        true,
+        true,
+    ))
+    .unwrap();
+    // nothing is running on mock, so round robin starts at ota_0 again
+    assert_eq!(flash.slice(ota_offset(0), update.len()), update);
+}